@@ -0,0 +1,117 @@
+//! World info activation over a large imported lorebook, the way a send runs
+//! it: one book of 5,000 entries, scanned against the default depth of recent
+//! messages, with and without a token budget and timed effects. Fails if a
+//! median run takes longer than LIMIT.
+//!
+//!     cargo bench --features bench --bench world_info_activation
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use ryokan_lib::bench::{Book, Conditions, WorldInfoEntry};
+use serde_json::json;
+
+const ENTRIES: usize = 5_000;
+const RUNS: usize = 200;
+const LIMIT: Duration = Duration::from_millis(5);
+
+/// Keys are unique and never contain one another ("term17x" isn't part of
+/// "term170x"), so only the entries the chat names activate.
+fn entry(i: usize) -> WorldInfoEntry {
+    let mut value = json!({
+        "id": format!("entry-{}", i),
+        "keys": [format!("term{}x", i), format!("Alias {} Prime", i)],
+        "content": format!("Entry {} describes a place, a person and what they have to do with each other.", i),
+        "enabled": true,
+        "comment": format!("Entry {}", i),
+        "position": "before",
+    });
+    // The options large imported books use, spread over the entries.
+    match i % 10 {
+        0 => value["case_sensitive"] = json!(true),
+        1 => value["match_whole_words"] = json!(true),
+        2 => {
+            value["secondary_keys"] = json!([format!("detail{}x", i)]);
+            value["selective_logic"] = json!("and_all");
+        }
+        _ => {}
+    }
+    // Timed effects, which only matter under a timeline.
+    match i % 7 {
+        0 => value["sticky"] = json!(2),
+        1 => value["cooldown"] = json!(3),
+        2 => value["delay"] = json!(20),
+        _ => {}
+    }
+    if i.is_multiple_of(500) {
+        value["keys"] = json!([format!("/rune{}[a-z]*/i", i)]);
+    }
+    serde_json::from_value(value).expect("valid entry")
+}
+
+/// Ten messages of ordinary prose mentioning a dozen entries.
+fn chat() -> Vec<String> {
+    let mentions = [17, 250, 1_001, 1_492, 2_002, 2_500, 3_333, 4_021, 4_444, 4_999, 12, 3_001];
+    (0..10)
+        .map(|m| {
+            let prose = "The rain had not stopped since the caravan reached the old harbour, and \
+                         everyone in the tavern seemed to have an opinion about why. ";
+            let named = mentions
+                .iter()
+                .skip(m)
+                .step_by(5)
+                .map(|i| format!("term{}x and Alias {} Prime, detail{}x", i, i, i))
+                .collect::<Vec<_>>()
+                .join("; ");
+            format!("{}{}{}", prose.repeat(4), named, " Rune2500stone glowed.".repeat(m % 2))
+        })
+        .collect()
+}
+
+fn measure(book: &Book, messages: &[&str], max_recursion: u32, conditions: &Conditions) -> (usize, Duration, Duration) {
+    // Warm-up, which also loads the tokenizer.
+    let activated = book.activate_with(messages, max_recursion, conditions);
+
+    let mut times: Vec<Duration> = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(book.activate_with(black_box(messages), max_recursion, conditions));
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+    (activated, times[RUNS / 2], times[RUNS * 95 / 100])
+}
+
+fn main() {
+    let start = Instant::now();
+    let book = Book::new((0..ENTRIES).map(entry).collect());
+    println!("matcher build, {} entries: {:?}", ENTRIES, start.elapsed());
+
+    let messages = chat();
+    let messages: Vec<&str> = messages.iter().map(String::as_str).collect();
+
+    // A budget that cuts off part of what the chat triggers, a hundred
+    // sticky entries and two of the mentioned ones on cooldown.
+    let sticky: Vec<String> = (0..ENTRIES).step_by(50).map(|i| format!("entry-{}", i)).collect();
+    let cooldown = vec!["entry-17".to_string(), "entry-3333".to_string()];
+    let timed = Conditions { token_budget: Some(600), sticky: &sticky, cooldown: &cooldown };
+    let cases = [("no budget", Conditions::default()), ("budget and timeline", timed)];
+
+    let mut failed = false;
+    for (name, conditions) in &cases {
+        for max_recursion in [0, 3] {
+            let (activated, median, p95) = measure(&book, &messages, max_recursion, conditions);
+            println!(
+                "activation, {}, recursion {}: {} activated, median {:?}, p95 {:?}",
+                name, max_recursion, activated, median, p95
+            );
+            failed |= median > LIMIT;
+        }
+    }
+
+    if failed {
+        eprintln!("median above the {:?} limit", LIMIT);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,455 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::error::AiError;
+use super::provider::{ProviderBackend, StreamDelta};
+use super::stats::Usage;
+use super::{AiRequest, CLIENT};
+
+/// Native Anthropic Messages API (`/v1/messages`).
+pub(crate) struct Anthropic;
+
+const API_VERSION: &str = "2023-06-01";
+
+// The Messages API rejects requests without max_tokens, unlike the OpenAI dialect.
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+// Anthropic refuses thinking budgets below this.
+const MIN_THINKING_BUDGET: u32 = 1024;
+
+/// Streaming events of the Messages API. Only the ones that carry text, usage or
+/// end the stream are modelled; content_block_start/stop and ping fall into `Other`.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamEvent {
+    /// Carries the prompt token count.
+    MessageStart { message: StartMessage },
+    ContentBlockDelta { delta: BlockDelta },
+    /// Carries the final output token count.
+    MessageDelta { usage: Option<ApiUsage> },
+    MessageStop,
+    Error { error: ErrorBody },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BlockDelta {
+    TextDelta { text: String },
+    ThinkingDelta { thinking: String },
+    // signature_delta, input_json_delta
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct StartMessage {
+    usage: Option<ApiUsage>,
+}
+
+#[derive(Deserialize)]
+struct ApiUsage {
+    input_tokens: Option<u32>,
+    output_tokens: Option<u32>,
+}
+
+impl ApiUsage {
+    fn into_usage(self) -> Usage {
+        Usage {
+            prompt_tokens: self.input_tokens,
+            completion_tokens: self.output_tokens,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    #[serde(rename = "type")]
+    kind: String,
+    message: String,
+}
+
+impl ErrorBody {
+    /// Maps Anthropic's in-band error types onto the same variants an HTTP status
+    /// would have produced.
+    fn into_ai_error(self) -> AiError {
+        let message = self.message;
+        match self.kind.as_str() {
+            "overloaded_error" => AiError::ServerOverloaded { message },
+            "rate_limit_error" => AiError::RateLimited { retry_after_secs: None, message },
+            "authentication_error" | "permission_error" => AiError::Auth { message },
+            _ => AiError::Api { status: None, message },
+        }
+    }
+}
+
+/// Splits OpenAI-style messages into Anthropic's separate `system` string and a
+/// user/assistant list. Consecutive turns of the same role are merged, since the
+/// frontend can produce them (e.g. world info attached as its own user turn).
+fn split_messages(messages: &[Value]) -> (String, Vec<Value>) {
+    let mut system_parts: Vec<&str> = Vec::new();
+    let mut turns: Vec<(String, String)> = Vec::new();
+
+    for msg in messages {
+        let role = msg["role"].as_str().unwrap_or("user");
+        let content = msg["content"].as_str().unwrap_or_default();
+
+        if role == "system" {
+            system_parts.push(content);
+            continue;
+        }
+
+        let role = if role == "assistant" { "assistant" } else { "user" };
+        match turns.last_mut() {
+            Some((last_role, last_content)) if last_role == role => {
+                last_content.push_str("\n\n");
+                last_content.push_str(content);
+            }
+            _ => turns.push((role.to_string(), content.to_string())),
+        }
+    }
+
+    let turns = turns
+        .into_iter()
+        .map(|(role, content)| serde_json::json!({ "role": role, "content": content }))
+        .collect();
+
+    (system_parts.join("\n\n"), turns)
+}
+
+fn with_auth(req: reqwest::RequestBuilder, api_key: &str) -> reqwest::RequestBuilder {
+    req.header("x-api-key", api_key)
+        .header("anthropic-version", API_VERSION)
+}
+
+impl ProviderBackend for Anthropic {
+    fn build_request(&self, payload: &AiRequest) -> Result<reqwest::RequestBuilder, String> {
+        let (system, mut messages) = split_messages(&payload.messages);
+        // A trailing assistant turn is continued natively, but the API rejects
+        // one that ends in whitespace.
+        if let Some(last) = messages.last_mut().filter(|m| m["role"] == "assistant") {
+            let trimmed = last["content"].as_str().unwrap_or_default().trim_end().to_string();
+            last["content"] = serde_json::json!(trimmed);
+        }
+        let max_tokens = payload.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS);
+
+        let mut body = serde_json::json!({
+            "model": payload.model,
+            "messages": messages,
+            "max_tokens": max_tokens,
+            "stream": true
+        });
+
+        if !system.is_empty() {
+            body["system"] = serde_json::json!(system);
+        }
+
+        if payload.is_thinking_model {
+            // budget_tokens counts towards max_tokens and has to stay below it,
+            // so there has to be room for the smallest budget and a reply.
+            if max_tokens <= MIN_THINKING_BUDGET {
+                return Err(format!(
+                    "Extended thinking needs max_tokens above {} (got {}).",
+                    MIN_THINKING_BUDGET, max_tokens
+                ));
+            }
+            let budget = payload
+                .thinking_budget
+                .unwrap_or(MIN_THINKING_BUDGET)
+                .max(MIN_THINKING_BUDGET)
+                .min(max_tokens.saturating_sub(1));
+            body["thinking"] = serde_json::json!({ "type": "enabled", "budget_tokens": budget });
+            // Extended thinking rejects custom temperature / top_k / top_p, so the
+            // sampler settings are only sent when it's off.
+        } else {
+            // Anthropic's temperature range is 0..1, not OpenAI's 0..2.
+            body["temperature"] = serde_json::json!(payload.temperature.clamp(0.0, 1.0));
+            if let Some(top_p) = payload.top_p {
+                body["top_p"] = serde_json::json!(top_p);
+            }
+            if let Some(top_k) = payload.top_k {
+                body["top_k"] = serde_json::json!(top_k);
+            }
+        }
+
+        Ok(with_auth(CLIENT.post(format!("{}/messages", payload.url)), &payload.api_key)
+            .json(&body))
+    }
+
+    fn parse_event(&self, _event: &str, data: &str) -> Result<StreamDelta, String> {
+        let event: StreamEvent = serde_json::from_str(data).map_err(|e| e.to_string())?;
+        let mut delta = StreamDelta::default();
+        match event {
+            StreamEvent::MessageStart { message } => {
+                // output_tokens here is a placeholder (usually 1); the real count
+                // follows in message_delta, so only the prompt side is kept.
+                delta.usage = message.usage.map(|u| Usage {
+                    prompt_tokens: u.input_tokens,
+                    ..Default::default()
+                });
+            }
+            StreamEvent::MessageDelta { usage } => {
+                delta.usage = usage.map(ApiUsage::into_usage);
+            }
+            StreamEvent::ContentBlockDelta { delta: BlockDelta::TextDelta { text } } => {
+                delta.content = Some(text);
+            }
+            StreamEvent::ContentBlockDelta { delta: BlockDelta::ThinkingDelta { thinking } } => {
+                delta.reasoning = Some(thinking);
+            }
+            StreamEvent::MessageStop => delta.done = true,
+            StreamEvent::Error { error } => {
+                delta.error = Some(error.into_ai_error());
+                delta.done = true;
+            }
+            StreamEvent::ContentBlockDelta { delta: BlockDelta::Other } | StreamEvent::Other => {}
+        }
+        Ok(delta)
+    }
+
+    fn models_request(&self, url: &str, api_key: &str) -> reqwest::RequestBuilder {
+        with_auth(CLIENT.get(format!("{}/models", url)), api_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::mock_server;
+    use serde_json::json;
+
+    fn parse(data: Value) -> StreamDelta {
+        Anthropic.parse_event("", &data.to_string()).unwrap()
+    }
+
+    #[test]
+    fn parses_text_and_thinking_deltas() {
+        let text = parse(json!({
+            "type": "content_block_delta",
+            "index": 0,
+            "delta": { "type": "text_delta", "text": "Hello" }
+        }));
+        assert_eq!(text.content.as_deref(), Some("Hello"));
+        assert!(text.reasoning.is_none());
+        assert!(!text.done);
+
+        let thinking = parse(json!({
+            "type": "content_block_delta",
+            "index": 0,
+            "delta": { "type": "thinking_delta", "thinking": "Hmm" }
+        }));
+        assert_eq!(thinking.reasoning.as_deref(), Some("Hmm"));
+        assert!(thinking.content.is_none());
+
+        let signature = parse(json!({
+            "type": "content_block_delta",
+            "index": 0,
+            "delta": { "type": "signature_delta", "signature": "abc" }
+        }));
+        assert!(signature.content.is_none() && signature.reasoning.is_none());
+    }
+
+    #[test]
+    fn parses_usage() {
+        let start = parse(json!({
+            "type": "message_start",
+            "message": { "usage": { "input_tokens": 120, "output_tokens": 1 } }
+        }));
+        let usage = start.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, Some(120));
+        assert_eq!(usage.completion_tokens, None);
+
+        let end = parse(json!({
+            "type": "message_delta",
+            "delta": { "stop_reason": "end_turn" },
+            "usage": { "output_tokens": 42 }
+        }));
+        assert_eq!(end.usage.unwrap().completion_tokens, Some(42));
+        assert!(!end.done);
+
+        assert!(parse(json!({ "type": "message_stop" })).done);
+        assert!(!parse(json!({ "type": "ping" })).done);
+    }
+
+    #[test]
+    fn parses_errors() {
+        let delta = parse(json!({
+            "type": "error",
+            "error": { "type": "overloaded_error", "message": "Overloaded" }
+        }));
+        assert!(delta.done);
+        assert!(matches!(delta.error, Some(AiError::ServerOverloaded { .. })));
+
+        let delta = parse(json!({
+            "type": "error",
+            "error": { "type": "invalid_request_error", "message": "Bad" }
+        }));
+        assert!(matches!(delta.error, Some(AiError::Api { status: None, .. })));
+
+        assert!(Anthropic.parse_event("", "not json").is_err());
+    }
+
+    fn request(url: &str, messages: Value) -> AiRequest {
+        serde_json::from_value(json!({
+            "provider": "anthropic",
+            "url": url,
+            "api_key": "sk-test",
+            "model": "claude",
+            "messages": messages,
+            "temperature": 1.5,
+            "top_k": 40,
+            "is_thinking_model": false,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn builds_a_messages_request() {
+        let payload = request(
+            "https://api.anthropic.com/v1",
+            json!([
+                { "role": "system", "content": "Be Alice." },
+                { "role": "user", "content": "Hi" },
+                { "role": "user", "content": "Who are you?" },
+                { "role": "system", "content": "Stay in character." },
+                { "role": "assistant", "content": "I am " },
+            ]),
+        );
+        let request = Anthropic.build_request(&payload).unwrap().build().unwrap();
+        assert_eq!(request.url().as_str(), "https://api.anthropic.com/v1/messages");
+        assert_eq!(request.headers()["x-api-key"], "sk-test");
+        assert_eq!(request.headers()["anthropic-version"], API_VERSION);
+
+        let body: Value = serde_json::from_slice(request.body().unwrap().as_bytes().unwrap()).unwrap();
+        assert_eq!(body["system"], "Be Alice.\n\nStay in character.");
+        assert_eq!(body["messages"], json!([
+            { "role": "user", "content": "Hi\n\nWho are you?" },
+            { "role": "assistant", "content": "I am" },
+        ]));
+        assert_eq!(body["max_tokens"], DEFAULT_MAX_TOKENS);
+        assert_eq!(body["temperature"], 1.0);
+        assert_eq!(body["top_k"], 40);
+        assert_eq!(body["stream"], true);
+        assert!(body.get("thinking").is_none());
+    }
+
+    #[tokio::test]
+    async fn streams_a_reply_from_a_local_server() {
+        let (url, server) = mock_server::serve(vec![mock_server::sse(concat!(
+            "event: message_start\n",
+            "data: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":12,\"output_tokens\":1}}}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"thinking_delta\",\"thinking\":\"Greet back.\"}}\n\n",
+            "event: ping\n",
+            "data: {\"type\":\"ping\"}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hello\"}}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"text_delta\",\"text\":\" there!\"}}\n\n",
+            "event: message_delta\n",
+            "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":9}}\n\n",
+            "event: message_stop\n",
+            "data: {\"type\":\"message_stop\"}\n\n",
+        ))]);
+        let payload = request(&url, json!([{ "role": "user", "content": "Hi" }]));
+        let res = Anthropic.build_request(&payload).unwrap().send().await.unwrap();
+
+        let mut events = crate::ai::stream::events(res, Anthropic.stream_format());
+        let (mut reply, mut reasoning, mut usage, mut done) = (String::new(), String::new(), Vec::new(), false);
+        while let Some(event) = futures::StreamExt::next(&mut events).await {
+            let event = event.unwrap();
+            let delta = Anthropic.parse_event(&event.event, &event.data).unwrap();
+            reply.extend(delta.content);
+            reasoning.extend(delta.reasoning);
+            usage.extend(delta.usage);
+            if delta.done {
+                done = true;
+                break;
+            }
+        }
+        assert!(done);
+        assert_eq!(reply, "Hello there!");
+        assert_eq!(reasoning, "Greet back.");
+        assert_eq!(usage[0].prompt_tokens, Some(12));
+        assert_eq!(usage[1].completion_tokens, Some(9));
+
+        let received = server.join().unwrap().remove(0);
+        assert!(received.starts_with("POST /v1/messages HTTP/1.1"));
+        assert!(received.contains("x-api-key: sk-test"));
+    }
+
+    fn thinking_request(max_tokens: u32, thinking_budget: u32) -> AiRequest {
+        serde_json::from_value(json!({
+            "provider": "anthropic",
+            "url": "https://api.anthropic.com/v1",
+            "api_key": "",
+            "model": "claude",
+            "messages": [{ "role": "user", "content": "Hi" }],
+            "temperature": 1.0,
+            "max_tokens": max_tokens,
+            "is_thinking_model": true,
+            "thinking_budget": thinking_budget,
+        }))
+        .unwrap()
+    }
+
+    fn body(request: reqwest::RequestBuilder) -> Value {
+        let request = request.build().unwrap();
+        serde_json::from_slice(request.body().unwrap().as_bytes().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn keeps_the_thinking_budget_within_bounds() {
+        let low = body(Anthropic.build_request(&thinking_request(4096, 100)).unwrap());
+        assert_eq!(low["thinking"]["budget_tokens"], 1024);
+        let high = body(Anthropic.build_request(&thinking_request(4096, 8000)).unwrap());
+        assert_eq!(high["thinking"]["budget_tokens"], 4095);
+        assert!(high.get("temperature").is_none());
+    }
+
+    #[test]
+    fn rejects_thinking_without_room_for_the_minimum_budget() {
+        assert!(Anthropic.build_request(&thinking_request(MIN_THINKING_BUDGET, 1024)).is_err());
+        assert!(Anthropic.build_request(&thinking_request(500, 1024)).is_err());
+    }
+
+    #[test]
+    fn splits_out_the_system_prompt() {
+        let (system, turns) = split_messages(&[
+            json!({ "role": "system", "content": "Be Alice." }),
+            json!({ "role": "user", "content": "Hi" }),
+            json!({ "role": "system", "content": "Stay in character." }),
+        ]);
+        assert_eq!(system, "Be Alice.\n\nStay in character.");
+        assert_eq!(turns, vec![json!({ "role": "user", "content": "Hi" })]);
+    }
+
+    #[test]
+    fn merges_turns_of_the_same_role() {
+        let (system, turns) = split_messages(&[
+            json!({ "role": "user", "content": "Context" }),
+            json!({ "role": "user", "content": "Hi" }),
+            json!({ "role": "assistant", "content": "Hello" }),
+            json!({ "role": "assistant", "content": "How are you?" }),
+            json!({ "role": "user", "content": "Fine" }),
+        ]);
+        assert!(system.is_empty());
+        assert_eq!(turns, vec![
+            json!({ "role": "user", "content": "Context\n\nHi" }),
+            json!({ "role": "assistant", "content": "Hello\n\nHow are you?" }),
+            json!({ "role": "user", "content": "Fine" }),
+        ]);
+    }
+
+    #[test]
+    fn keeps_a_trailing_assistant_turn_as_prefill() {
+        let (_, turns) = split_messages(&[
+            json!({ "role": "user", "content": "Hi" }),
+            json!({ "role": "assistant", "content": "Hello, " }),
+        ]);
+        assert_eq!(turns.len(), 2);
+        assert_eq!(turns[1], json!({ "role": "assistant", "content": "Hello, " }));
+    }
+}
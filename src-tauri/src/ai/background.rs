@@ -0,0 +1,149 @@
+use serde::Serialize;
+use tauri::{Emitter, Manager, Window};
+
+use crate::database::generation_requests;
+use crate::database::messages::{self, GenerationStatus};
+use crate::database::world_info::timed_effects;
+
+use super::{
+    stream_generation, AiError, AiRequest, ChoiceOutcome, GenerationStats, StartedGeneration,
+    CHAT_CHANNEL,
+};
+
+/// Returned as soon as a backend-owned generation has started.
+#[derive(Serialize)]
+pub struct BackgroundGeneration {
+    pub generation_id: String,
+    /// The message being written into: a new one, or the one given by the caller.
+    pub message_id: String,
+    /// Index of the swipe variant being written into.
+    pub swipe_index: i64,
+}
+
+/// Emitted as "ai-generation-finished" once a backend-owned generation has
+/// written its last state to the database.
+#[derive(Serialize, Clone)]
+struct GenerationFinishedPayload {
+    generation_id: String,
+    message_id: String,
+    swipe_index: i64,
+    status: GenerationStatus,
+    /// True if nothing was generated and the empty message/variant was removed again.
+    discarded: bool,
+    stats: Option<GenerationStats>,
+    error: Option<AiError>,
+}
+
+/// Starts a generation that writes its reply straight into the messages table,
+/// so it survives the chat being closed or the webview reloading mid-stream.
+/// Without `message_id` a new assistant message is appended to `chat_id`;
+/// with it, the reply becomes a new swipe variant of that message.
+/// Tokens are still emitted as "ai-token" events for live display. The
+/// accumulated text is saved periodically while streaming, and the row is
+/// marked complete or interrupted at the end.
+#[tauri::command]
+pub async fn generate_into_chat(
+    window: Window,
+    mut payload: AiRequest,
+    chat_id: String,
+    message_id: Option<String>,
+) -> Result<BackgroundGeneration, AiError> {
+    let app = window.app_handle().clone();
+    // Without prebuilt messages the prompt comes from this chat, and a new
+    // variant must not see the text of the message it is written into.
+    payload.chat_id.get_or_insert_with(|| chat_id.clone());
+    if let Some(id) = &message_id {
+        payload.prompt_options_mut().before_message_id.get_or_insert_with(|| id.clone());
+    }
+    // Registered before the prompt is assembled and kept past returning, so a
+    // stop_generation() at any point can't miss it.
+    let StartedGeneration { id: generation_id, guard, token, timed } = payload.start(&app).await?;
+    // Nothing has been written yet, so a stop this early leaves no message behind.
+    let timed = timed.ok_or(AiError::Cancelled)?;
+
+    let internal = |message: String| AiError::Internal { message };
+    let (message_id, swipe_index) = match message_id {
+        Some(id) => {
+            let index = messages::start_streaming_variant(&app, &id).map_err(internal)?;
+            (id, index)
+        }
+        None => (messages::start_streaming_message(&app, &chat_id).map_err(internal)?, 0),
+    };
+
+    let started = BackgroundGeneration {
+        generation_id: generation_id.clone(),
+        message_id: message_id.clone(),
+        swipe_index,
+    };
+
+    tauri::async_runtime::spawn(async move {
+        // Keeps the generation registered until the spawned task is done.
+        let _guard = guard;
+
+        let mut save = |_: usize, text: &str| {
+            if let Err(e) = messages::save_variant_text(&app, &message_id, swipe_index, text) {
+                eprintln!("Failed to save streamed text: {}", e);
+            }
+        };
+        let choice_ids = [generation_id.clone()];
+        let outcome = stream_generation(
+            &window,
+            &payload,
+            &generation_id,
+            CHAT_CHANNEL,
+            &choice_ids,
+            &token,
+            &mut save,
+        )
+        .await;
+
+        let (choice, error) = match outcome {
+            Ok(outcome) => (outcome.choices.into_iter().next().unwrap_or_default(), outcome.error),
+            Err(e) => (ChoiceOutcome::default(), Some(e)),
+        };
+        let ChoiceOutcome { reply, reasoning, stats } = choice;
+        let status = if error.is_some() || token.is_cancelled() {
+            GenerationStatus::Interrupted
+        } else {
+            GenerationStatus::Complete
+        };
+
+        let discarded = reply.is_empty();
+        let written = if discarded {
+            messages::discard_variant(&app, &message_id, swipe_index)
+        } else {
+            messages::finish_variant(
+                &app,
+                &message_id,
+                swipe_index,
+                stats.as_ref(),
+                Some(reasoning).filter(|r| !r.is_empty()),
+                status,
+            )
+            .and_then(|_| {
+                generation_requests::link_request(&app, &generation_id, &message_id, Some(swipe_index))
+            })
+            // Only a finished reply starts the sticky / cooldown windows of
+            // the world info it triggered.
+            .and_then(|_| match status {
+                GenerationStatus::Complete => timed_effects::record_started(&app, &timed),
+                _ => Ok(()),
+            })
+        };
+        if let Err(e) = written {
+            eprintln!("Failed to finalize generation {}: {}", generation_id, e);
+        }
+
+        let _ = window.emit("ai-generation-finished", GenerationFinishedPayload {
+            generation_id,
+            message_id,
+            swipe_index,
+            status,
+            discarded,
+            stats,
+            error,
+        });
+    });
+
+    Ok(started)
+}
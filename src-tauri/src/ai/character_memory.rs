@@ -0,0 +1,99 @@
+use serde::Serialize;
+use tauri::{Manager, Window};
+
+use crate::database::character_memories::{self, CharacterMemory};
+use crate::prompt::character_memory::{extraction_prompt, parse_notes};
+
+use super::{register_generation, stream_generation, AiError, AiRequest, EventChannel};
+
+/// Context window assumed when the request doesn't say (`num_ctx`); the
+/// frontend's default as well.
+const DEFAULT_CONTEXT_LIMIT: u32 = 4096;
+
+/// Its own events, so the extraction doesn't show up in a reply streaming at
+/// the same time.
+const MEMORY_CHANNEL: EventChannel = EventChannel {
+    token: "ai-memory-token",
+    thinking: "ai-memory-thinking-token",
+};
+
+#[derive(Serialize)]
+pub struct ExtractMemoriesResult {
+    pub generation_id: String,
+    /// The notes that were saved; empty if the model found nothing new.
+    pub memories: Vec<CharacterMemory>,
+    /// Nothing is saved for a stopped extraction.
+    pub cancelled: bool,
+}
+
+/// Asks the model (`payload`'s settings; its messages are replaced) what the
+/// character of `chat_id` should remember from the chat in later ones, and
+/// saves each new point as a character memory. With `role_id`, the notes are
+/// about that persona and only used with it; otherwise they apply whichever
+/// persona the user plays. Tokens stream as "ai-memory-token" events.
+#[tauri::command]
+pub async fn extract_character_memories(
+    window: Window,
+    mut payload: AiRequest,
+    chat_id: String,
+    role_id: Option<String>,
+) -> Result<ExtractMemoriesResult, AiError> {
+    let app = window.app_handle().clone();
+    let mut options = payload.prompt_options.clone().unwrap_or_default();
+    if role_id.is_some() {
+        options.role_id = role_id.clone();
+    }
+    options.model.get_or_insert_with(|| payload.model.clone());
+    // The prompt gets the context window minus what the reply may use.
+    let context = payload.num_ctx.unwrap_or(DEFAULT_CONTEXT_LIMIT);
+    let token_budget = context.saturating_sub(payload.max_tokens.unwrap_or(0));
+    let extraction = extraction_prompt(&app, &chat_id, &options, token_budget)
+        .map_err(|message| AiError::InvalidRequest { message })?;
+
+    // A single user message: some local servers produce nothing for a
+    // request that has a system message but no user turn.
+    payload.messages = vec![serde_json::json!({
+        "role": "user",
+        "content": extraction.prompt,
+    })];
+    payload.chat_id = None;
+    payload.n = None;
+
+    let generation_id = payload
+        .generation_id
+        .get_or_insert_with(|| uuid::Uuid::new_v4().to_string())
+        .clone();
+    let (_guard, token) =
+        register_generation(&generation_id, &payload.model, payload.label.clone())?;
+
+    let choice_ids = [generation_id.clone()];
+    let outcome = stream_generation(
+        &window,
+        &payload,
+        &generation_id,
+        MEMORY_CHANNEL,
+        &choice_ids,
+        &token,
+        &mut |_, _| {},
+    )
+    .await?;
+    if let Some(error) = outcome.error {
+        return Err(error);
+    }
+    if outcome.cancelled {
+        return Ok(ExtractMemoriesResult { generation_id, memories: Vec::new(), cancelled: true });
+    }
+
+    let reply = outcome.choices.into_iter().next().unwrap_or_default().reply;
+    let notes = parse_notes(&reply, &extraction.known);
+    let memories = character_memories::insert_memories(
+        &app,
+        &extraction.character_id,
+        role_id.as_deref(),
+        Some(&chat_id),
+        &notes,
+    )
+    .map_err(|message| AiError::Internal { message })?;
+
+    Ok(ExtractMemoriesResult { generation_id, memories, cancelled: false })
+}
@@ -0,0 +1,173 @@
+use serde::Serialize;
+use tauri::{Manager, Window};
+
+use crate::database::messages;
+
+use super::{
+    stream_generation, AiError, AiRequest, GenerationStats, StartedGeneration, CHAT_CHANNEL,
+};
+
+#[derive(Serialize)]
+pub struct ContinueResult {
+    pub generation_id: String,
+    /// The message's full text after continuing, as saved.
+    pub content: String,
+    /// Stats of the continuation alone, not the whole message.
+    pub stats: Option<GenerationStats>,
+    pub cancelled: bool,
+}
+
+/// Joins the existing text and its continuation. Models often start a
+/// continuation with the space or newline that separates it from the prefill;
+/// trailing whitespace on the prefill (which Anthropic strips anyway) would
+/// double it.
+fn join_continuation(existing: &str, continuation: &str) -> String {
+    if continuation.starts_with(char::is_whitespace) {
+        format!("{}{}", existing.trim_end(), continuation)
+    } else {
+        format!("{}{}", existing, continuation)
+    }
+}
+
+/// Ends `messages` with the text being continued as an assistant turn.
+/// A prompt built from the chat stops before that message, so the prefill
+/// goes after everything in it, depth-0 injections included. A prompt the
+/// frontend sent may or may not end with the message already; the database
+/// is the source of truth for its text either way.
+fn add_prefill(messages: &mut Vec<serde_json::Value>, existing: &str, built_from_chat: bool) {
+    let prefill = serde_json::json!({ "role": "assistant", "content": existing });
+    match messages.last_mut() {
+        Some(last) if !built_from_chat && last["role"] == "assistant" => *last = prefill,
+        _ => messages.push(prefill),
+    }
+}
+
+/// Extends an assistant message that got cut off (e.g. at max_tokens).
+/// Its active text is sent as a prefill: a trailing assistant turn for chat
+/// providers, an open assistant turn in text-completion mode. New tokens stream
+/// as "ai-token" events and are appended to the active variant once the
+/// stream ends, including after a stop or a failure part-way through, along
+/// with the continuation's stats and reasoning.
+#[tauri::command]
+pub async fn continue_generation(
+    window: Window,
+    mut payload: AiRequest,
+    message_id: String,
+) -> Result<ContinueResult, AiError> {
+    let app = window.app_handle().clone();
+    let internal = |message: String| AiError::Internal { message };
+
+    let (role, existing) = messages::get_message_content(&app, &message_id).map_err(internal)?;
+    if role != "assistant" {
+        return Err(AiError::InvalidRequest {
+            message: "Only assistant messages can be continued".to_string(),
+        });
+    }
+
+    // A prompt built from the chat ends right before the message itself.
+    let built_from_chat = payload.chat_id.is_some() && payload.messages.is_empty();
+    payload.prompt_options_mut().before_message_id = Some(message_id.clone());
+    let StartedGeneration { id: generation_id, guard: _guard, token, timed } = payload.start(&app).await?;
+    if timed.is_none() {
+        return Ok(ContinueResult {
+            generation_id,
+            content: existing,
+            stats: None,
+            cancelled: true,
+        });
+    }
+    add_prefill(&mut payload.messages, &existing, built_from_chat);
+    payload.continue_last = true;
+
+    let choice_ids = [generation_id.clone()];
+    let outcome = stream_generation(
+        &window,
+        &payload,
+        &generation_id,
+        CHAT_CHANNEL,
+        &choice_ids,
+        &token,
+        &mut |_, _| {},
+    )
+    .await?;
+    let choice = outcome.choices.into_iter().next().unwrap_or_default();
+
+    let content = if choice.reply.is_empty() {
+        existing
+    } else {
+        let joined = join_continuation(&existing, &choice.reply);
+        messages::save_continuation(
+            &app,
+            &message_id,
+            &joined,
+            choice.stats.as_ref(),
+            &choice.reasoning,
+            &generation_id,
+        )
+        .map_err(internal)?;
+        joined
+    };
+
+    // Reported after saving, so whatever arrived before the failure is kept.
+    if let Some(error) = outcome.error {
+        return Err(error);
+    }
+
+    Ok(ContinueResult {
+        generation_id,
+        content,
+        stats: choice.stats,
+        cancelled: outcome.cancelled,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn contents(messages: &[serde_json::Value]) -> Vec<(&str, &str)> {
+        messages
+            .iter()
+            .map(|m| (m["role"].as_str().unwrap(), m["content"].as_str().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn prefill_follows_a_depth_zero_injection() {
+        // What assemble gives for the history before the continued message,
+        // with a world info entry at depth 0 after the last turn.
+        let mut messages = vec![
+            json!({ "role": "system", "content": "You are Kael." }),
+            json!({ "role": "user", "content": "Tell me about the guild." }),
+            json!({ "role": "system", "content": "The guild hall burned down." }),
+        ];
+        add_prefill(&mut messages, "The guild was", true);
+        assert_eq!(contents(&messages), [
+            ("system", "You are Kael."),
+            ("user", "Tell me about the guild."),
+            ("system", "The guild hall burned down."),
+            ("assistant", "The guild was"),
+        ]);
+    }
+
+    #[test]
+    fn prefill_replaces_the_message_in_a_sent_prompt() {
+        let mut messages = vec![
+            json!({ "role": "user", "content": "Hi" }),
+            json!({ "role": "assistant", "content": "Hel" }),
+        ];
+        add_prefill(&mut messages, "Hello th", false);
+        assert_eq!(contents(&messages), [("user", "Hi"), ("assistant", "Hello th")]);
+
+        let mut messages = vec![json!({ "role": "user", "content": "Hi" })];
+        add_prefill(&mut messages, "Hello th", false);
+        assert_eq!(contents(&messages), [("user", "Hi"), ("assistant", "Hello th")]);
+    }
+
+    #[test]
+    fn continuation_joins_without_doubled_whitespace() {
+        assert_eq!(join_continuation("Hello ", " there"), "Hello there");
+        assert_eq!(join_continuation("Hello", "there"), "Hellothere");
+    }
+}
@@ -0,0 +1,85 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+use super::CLIENT;
+
+/// Local servers embedding a large lorebook for the first time can take a
+/// while; anything beyond this is treated as down.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// An OpenAI-compatible `/embeddings` endpoint (OpenAI, LM Studio, llama.cpp,
+/// Ollama's /v1 shim, ...). `url` is the base the endpoint hangs off, the same
+/// kind of URL the chat settings store.
+pub(crate) struct EmbeddingConfig {
+    pub url: String,
+    pub api_key: String,
+    pub model: String,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+/// Embeds `inputs` in one request. Vectors come back in input order.
+pub(crate) async fn embed(config: &EmbeddingConfig, inputs: &[&str]) -> Result<Vec<Vec<f32>>, String> {
+    let mut req = CLIENT
+        .post(format!("{}/embeddings", config.url.trim_end_matches('/')))
+        .timeout(REQUEST_TIMEOUT)
+        .json(&serde_json::json!({
+            "model": config.model,
+            "input": inputs,
+        }));
+    if !config.api_key.is_empty() {
+        req = req.bearer_auth(&config.api_key);
+    }
+
+    let res = req
+        .send()
+        .await
+        .map_err(|e| format!("Embedding request failed: {}", e))?;
+    let status = res.status();
+    let body = res
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read embedding response: {}", e))?;
+    if !status.is_success() {
+        let preview: String = body.chars().take(500).collect();
+        return Err(format!("Embedding API error: Status {}: {}", status, preview));
+    }
+
+    let mut parsed: EmbeddingResponse = serde_json::from_str(&body)
+        .map_err(|e| format!("Failed to parse embedding response: {}", e))?;
+    if parsed.data.len() != inputs.len() {
+        return Err(format!(
+            "Embedding API returned {} vectors for {} inputs",
+            parsed.data.len(),
+            inputs.len()
+        ));
+    }
+    parsed.data.sort_by_key(|d| d.index);
+    Ok(parsed.data.into_iter().map(|d| d.embedding).collect())
+}
+
+/// Cosine similarity in [-1, 1]; 0 for empty or mismatched vectors.
+pub(crate) fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let (mut dot, mut norm_a, mut norm_b) = (0.0f32, 0.0f32, 0.0f32);
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
@@ -0,0 +1,215 @@
+use serde::Serialize;
+use std::fmt;
+use std::time::Duration;
+
+/// Everything that can go wrong during a generation, in a shape the frontend can
+/// switch on (`kind`) instead of pattern-matching error strings.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AiError {
+    /// Missing or rejected API key (HTTP 401/403).
+    Auth { message: String },
+    /// HTTP 429. `retry_after_secs` is the server's Retry-After header, if sent.
+    RateLimited {
+        retry_after_secs: Option<u64>,
+        message: String,
+    },
+    /// The prompt doesn't fit the model's context window.
+    ContextOverflow { message: String },
+    /// The server is temporarily unable to take the request (HTTP 502/503/504/529).
+    ServerOverloaded { message: String },
+    /// The server couldn't be reached, or the connection broke.
+    Network { message: String },
+    /// Stopped via stop_generation().
+    Cancelled,
+    /// The stream contained something that isn't valid for the provider's protocol.
+    MalformedStream { message: String },
+    /// Any other error response from the API.
+    Api { status: Option<u16>, message: String },
+    /// The request couldn't be built (bad template, duplicate generation ID, ...).
+    InvalidRequest { message: String },
+    /// Failure on our side, e.g. emitting an event to the webview.
+    Internal { message: String },
+}
+
+impl fmt::Display for AiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AiError::Auth { message } => write!(f, "Authentication failed: {}", message),
+            AiError::RateLimited { retry_after_secs: Some(secs), message } => {
+                write!(f, "Rate limited (retry after {}s): {}", secs, message)
+            }
+            AiError::RateLimited { retry_after_secs: None, message } => {
+                write!(f, "Rate limited: {}", message)
+            }
+            AiError::ContextOverflow { message } => write!(f, "Context window exceeded: {}", message),
+            AiError::ServerOverloaded { message } => write!(f, "Server overloaded: {}", message),
+            AiError::Network { message } => write!(f, "Network error: {}", message),
+            AiError::Cancelled => write!(f, "Generation cancelled"),
+            AiError::MalformedStream { message } => write!(f, "Malformed stream: {}", message),
+            AiError::Api { status: Some(status), message } => {
+                write!(f, "API error: Status {}: {}", status, message)
+            }
+            AiError::Api { status: None, message } => write!(f, "API error: {}", message),
+            AiError::InvalidRequest { message } => write!(f, "Invalid request: {}", message),
+            AiError::Internal { message } => write!(f, "Internal error: {}", message),
+        }
+    }
+}
+
+impl From<tauri::Error> for AiError {
+    fn from(e: tauri::Error) -> Self {
+        AiError::Internal { message: e.to_string() }
+    }
+}
+
+impl From<reqwest::Error> for AiError {
+    fn from(e: reqwest::Error) -> Self {
+        AiError::Network { message: e.to_string() }
+    }
+}
+
+// Phrases servers use for "prompt too long" on an otherwise generic 400.
+// llama.cpp, OpenAI, Anthropic, vLLM and Ollama respectively.
+const CONTEXT_OVERFLOW_HINTS: &[&str] = &[
+    "exceeds the available context size",
+    "context_length_exceeded",
+    "prompt is too long",
+    "maximum context length",
+    "context window",
+];
+
+impl AiError {
+    /// Whether re-sending the same request later can succeed. Only ever used before
+    /// the first byte of a response arrives, so nothing gets generated twice.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            AiError::RateLimited { .. } | AiError::ServerOverloaded { .. } | AiError::Network { .. }
+        )
+    }
+
+    /// The wait the server asked for, if any.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            AiError::RateLimited { retry_after_secs: Some(secs), .. } => {
+                Some(Duration::from_secs(*secs))
+            }
+            _ => None,
+        }
+    }
+
+    /// Classifies a non-success HTTP response, reading its body for the message.
+    pub async fn from_response(res: reqwest::Response) -> Self {
+        let status = res.status().as_u16();
+        let retry_after_secs = res
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok());
+        let body = res.text().await.unwrap_or_default();
+        Self::from_status(status, retry_after_secs, &body)
+    }
+
+    fn from_status(status: u16, retry_after_secs: Option<u64>, body: &str) -> Self {
+        let message = error_message(body).unwrap_or_else(|| format!("Status {}", status));
+        let lower = message.to_lowercase();
+
+        match status {
+            401 | 403 => AiError::Auth { message },
+            429 => AiError::RateLimited { retry_after_secs, message },
+            413 => AiError::ContextOverflow { message },
+            400 if CONTEXT_OVERFLOW_HINTS.iter().any(|hint| lower.contains(hint)) => {
+                AiError::ContextOverflow { message }
+            }
+            // 529 is Anthropic's "overloaded".
+            502 | 503 | 504 | 529 => AiError::ServerOverloaded { message },
+            _ => AiError::Api { status: Some(status), message },
+        }
+    }
+}
+
+/// Pulls a human-readable message out of an error body. Understands the common
+/// `{"error": {"message": ..}}`, `{"error": ".."}` and `{"message": ..}` shapes and
+/// falls back to a preview of the raw text.
+fn error_message(body: &str) -> Option<String> {
+    if let Ok(json) = serde_json::from_str::<serde_json::Value>(body) {
+        let message = json["error"]["message"]
+            .as_str()
+            .or_else(|| json["error"].as_str())
+            .or_else(|| json["message"].as_str());
+        if let Some(message) = message {
+            return Some(message.to_string());
+        }
+    }
+
+    let trimmed = body.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    // chars().take(n) instead of byte-slicing, so a multi-byte character at the cut
+    // can't cause a panic.
+    Some(trimmed.chars().take(500).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_context_overflow() {
+        assert!(matches!(AiError::from_status(413, None, ""), AiError::ContextOverflow { .. }));
+        for hint in CONTEXT_OVERFLOW_HINTS {
+            let body = format!(r#"{{"error": {{"message": "The request {}."}}}}"#, hint.to_uppercase());
+            let error = AiError::from_status(400, None, &body);
+            assert!(matches!(error, AiError::ContextOverflow { .. }), "{}", hint);
+        }
+        assert!(matches!(
+            AiError::from_status(400, None, r#"{"error": "Unknown model"}"#),
+            AiError::Api { status: Some(400), .. }
+        ));
+    }
+
+    #[test]
+    fn classifies_auth_and_overload() {
+        assert!(matches!(AiError::from_status(401, None, ""), AiError::Auth { .. }));
+        assert!(matches!(AiError::from_status(403, None, ""), AiError::Auth { .. }));
+        for status in [502, 503, 504, 529] {
+            assert!(matches!(AiError::from_status(status, None, ""), AiError::ServerOverloaded { .. }));
+        }
+    }
+
+    #[test]
+    fn rate_limits_carry_the_servers_wait() {
+        let error = AiError::from_status(429, Some(7), r#"{"message": "Slow down"}"#);
+        assert!(matches!(
+            &error,
+            AiError::RateLimited { retry_after_secs: Some(7), message } if message == "Slow down"
+        ));
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(7)));
+        assert!(error.is_retryable());
+        assert_eq!(AiError::from_status(429, None, "").retry_after(), None);
+    }
+
+    #[test]
+    fn only_transient_errors_are_retryable() {
+        assert!(AiError::from_status(503, None, "").is_retryable());
+        assert!(AiError::Network { message: String::new() }.is_retryable());
+        assert!(!AiError::from_status(401, None, "").is_retryable());
+        assert!(!AiError::from_status(413, None, "").is_retryable());
+        assert!(!AiError::MalformedStream { message: String::new() }.is_retryable());
+        assert!(!AiError::Cancelled.is_retryable());
+    }
+
+    #[test]
+    fn reads_the_message_from_common_body_shapes() {
+        assert_eq!(error_message(r#"{"error": {"message": "A"}}"#).as_deref(), Some("A"));
+        assert_eq!(error_message(r#"{"error": "B"}"#).as_deref(), Some("B"));
+        assert_eq!(error_message(r#"{"message": "C"}"#).as_deref(), Some("C"));
+        assert_eq!(error_message("  Bad gateway \n").as_deref(), Some("Bad gateway"));
+        assert_eq!(error_message(" "), None);
+        assert_eq!(error_message(&"é".repeat(600)).unwrap().chars().count(), 500);
+        let fallback = AiError::from_status(500, None, "");
+        assert!(matches!(fallback, AiError::Api { message, .. } if message == "Status 500"));
+    }
+}
@@ -0,0 +1,126 @@
+use serde::Serialize;
+use tauri::{Manager, Window};
+
+use crate::database::roles;
+
+use super::{
+    stream_generation, AiError, AiRequest, EventChannel, GenerationStats, StartedGeneration,
+};
+
+/// Separate from the chat reply's events, so a draft can't leak into a reply
+/// streaming at the same time (or a listener for one pick up the other).
+const IMPERSONATE_CHANNEL: EventChannel = EventChannel {
+    token: "ai-impersonate-token",
+    thinking: "ai-impersonate-thinking-token",
+};
+
+// Used when the role isn't stored (the frontend's built-in default role).
+const FALLBACK_NAME: &str = "User";
+
+#[derive(Serialize)]
+pub struct ImpersonateResult {
+    pub generation_id: String,
+    /// The drafted message. Not saved anywhere.
+    pub draft: String,
+    pub stats: Option<GenerationStats>,
+    pub cancelled: bool,
+}
+
+/// The instruction appended to the chat: whose turn it is, who they are, and
+/// what to leave out.
+fn impersonation_instruction(name: &str, pronouns: &str, bio: &str) -> String {
+    let mut identity = name.to_string();
+    if !pronouns.trim().is_empty() {
+        identity.push_str(&format!(" ({})", pronouns.trim()));
+    }
+
+    let mut instruction = format!(
+        "[Director's instruction, not part of the roleplay: for this reply only, do not \
+         write as your character. Write the next message of {} instead, in their \
+         voice and from their perspective, continuing the conversation naturally.",
+        identity
+    );
+    if !bio.trim().is_empty() {
+        instruction.push_str(&format!("\n\nAbout {}:\n{}", name, bio.trim()));
+    }
+    instruction.push_str(&format!(
+        "\n\nWrite only {}'s message. Do not write anyone else's reply, do not prefix it \
+         with a name, and do not comment on it.]",
+        name
+    ));
+    instruction
+}
+
+/// Removes a "Name:" prefix the model sometimes adds despite being told not to.
+fn strip_name_prefix<'a>(draft: &'a str, name: &str) -> &'a str {
+    let trimmed = draft.trim();
+    trimmed
+        .strip_prefix(name)
+        .and_then(|rest| rest.strip_prefix(':'))
+        .map(str::trim_start)
+        .unwrap_or(trimmed)
+}
+
+/// Drafts the user's next message from their persona (the DbRole `role_id`):
+/// the chat history in `payload.messages` gets an instruction appended that
+/// asks the model to write as that persona instead of its character.
+/// Tokens stream as "ai-impersonate-token" events; the draft is returned and
+/// not saved, so the user can edit it before sending.
+#[tauri::command]
+pub async fn impersonate(
+    window: Window,
+    mut payload: AiRequest,
+    role_id: Option<String>,
+) -> Result<ImpersonateResult, AiError> {
+    let app = window.app_handle().clone();
+    // A prompt built from the chat is written from the same persona.
+    if let Some(id) = &role_id {
+        payload.prompt_options_mut().role_id.get_or_insert_with(|| id.clone());
+    }
+    let StartedGeneration { id: generation_id, guard: _guard, token, timed } = payload.start(&app).await?;
+    if timed.is_none() {
+        return Ok(ImpersonateResult {
+            draft: String::new(),
+            generation_id,
+            stats: None,
+            cancelled: true,
+        });
+    }
+
+    let role = match role_id.as_deref() {
+        Some(id) => roles::get_role(&app, id).map_err(|message| AiError::Internal { message })?,
+        None => None,
+    };
+    let (name, pronouns, bio) = match role.as_ref() {
+        Some(role) => (role.name.as_str(), role.pronouns.as_str(), role.bio.as_str()),
+        None => (FALLBACK_NAME, "", ""),
+    };
+
+    payload.messages.push(serde_json::json!({
+        "role": "user",
+        "content": impersonation_instruction(name, pronouns, bio),
+    }));
+
+    let choice_ids = [generation_id.clone()];
+    let outcome = stream_generation(
+        &window,
+        &payload,
+        &generation_id,
+        IMPERSONATE_CHANNEL,
+        &choice_ids,
+        &token,
+        &mut |_, _| {},
+    )
+    .await?;
+    if let Some(error) = outcome.error {
+        return Err(error);
+    }
+    let choice = outcome.choices.into_iter().next().unwrap_or_default();
+
+    Ok(ImpersonateResult {
+        draft: strip_name_prefix(&choice.reply, name).to_string(),
+        generation_id,
+        stats: choice.stats,
+        cancelled: outcome.cancelled,
+    })
+}
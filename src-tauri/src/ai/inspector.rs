@@ -0,0 +1,76 @@
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{Manager, Window};
+
+use crate::database::generation_requests;
+
+use super::provider::ProviderBackend;
+use super::{AiRequest, Provider};
+
+const REDACTED: &str = "[redacted]";
+
+/// What the prompt inspector keeps of a request.
+#[derive(Serialize)]
+struct RecordedRequest<'a> {
+    provider: Provider,
+    method: String,
+    endpoint: String,
+    model: &'a str,
+    /// The JSON body exactly as sent: messages or rendered prompt, samplers, ...
+    body: Value,
+}
+
+/// Replaces `secret` wherever it appears in the string values of `value`.
+fn redact(value: &mut Value, secret: &str) {
+    match value {
+        Value::String(s) if s.contains(secret) => *s = s.replace(secret, REDACTED),
+        Value::Array(items) => items.iter_mut().for_each(|v| redact(v, secret)),
+        Value::Object(map) => map.values_mut().for_each(|v| redact(v, secret)),
+        _ => {}
+    }
+}
+
+fn snapshot(backend: &dyn ProviderBackend, payload: &AiRequest) -> Result<Value, String> {
+    let request = backend
+        .build_request(payload)?
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let mut body = match request.body().and_then(|b| b.as_bytes()) {
+        Some(bytes) => serde_json::from_slice(bytes)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(bytes).into_owned())),
+        None => Value::Null,
+    };
+    let mut endpoint = request.url().to_string();
+    if !payload.api_key.is_empty() {
+        redact(&mut body, &payload.api_key);
+        endpoint = endpoint.replace(&payload.api_key, REDACTED);
+    }
+
+    serde_json::to_value(RecordedRequest {
+        provider: payload.provider,
+        method: request.method().to_string(),
+        endpoint,
+        model: &payload.model,
+        body,
+    })
+    .map_err(|e| e.to_string())
+}
+
+/// Builds the request once more without sending it and stores it under every
+/// choice ID. Headers, where the API key goes, aren't kept at all; the key is
+/// also scrubbed from the URL and body in case a server expects it there.
+/// Failures are only logged, the inspector must never stop a generation.
+pub(crate) fn record(
+    window: &Window,
+    backend: &dyn ProviderBackend,
+    payload: &AiRequest,
+    choice_ids: &[String],
+) {
+    let recorded = snapshot(backend, payload).and_then(|request| {
+        generation_requests::record_request(window.app_handle(), choice_ids, &request)
+    });
+    if let Err(e) = recorded {
+        eprintln!("Failed to record generation request: {}", e);
+    }
+}
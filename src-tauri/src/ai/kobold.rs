@@ -0,0 +1,149 @@
+use serde::Deserialize;
+
+use super::provider::{native_base, ProviderBackend, StreamDelta};
+use super::templates::render_prompt;
+use super::{AiRequest, CLIENT};
+
+/// KoboldCpp's native `/api/extra/generate/stream` endpoint. Text completion only,
+/// but it exposes samplers the OpenAI shim hides (DRY, XTC, mirostat, sampler order).
+pub(crate) struct KoboldCpp;
+
+/// `data:` payload of one streamed token.
+#[derive(Deserialize)]
+struct TokenChunk {
+    #[serde(default)]
+    token: String,
+    finish_reason: Option<String>,
+}
+
+/// /api/v1/model response.
+#[derive(Deserialize)]
+struct ModelResponse {
+    result: String,
+}
+
+impl ProviderBackend for KoboldCpp {
+    fn build_request(&self, payload: &AiRequest) -> Result<reqwest::RequestBuilder, String> {
+        if payload.frequency_penalty.is_some_and(|p| p != 0.0) {
+            return Err(
+                "KoboldCpp has no frequency penalty; set it to 0 and use the repetition penalty."
+                    .to_string(),
+            );
+        }
+        // Text completion only, so the chat always goes through an instruct
+        // template; Alpaca unless the user picked one.
+        let rendered = render_prompt(
+            payload.instruct_template.unwrap_or_default(),
+            payload.custom_template.as_deref(),
+            &payload.messages,
+            payload.continue_last,
+        )?;
+
+        let mut body = serde_json::json!({
+            "prompt": rendered.prompt,
+            "temperature": payload.temperature,
+            "stop_sequence": rendered.stop,
+            // Lets /api/extra/abort target exactly this request when KoboldCpp
+            // runs in multiuser mode.
+            "genkey": payload.generation_id,
+        });
+
+        if let Some(max_tokens) = payload.max_tokens {
+            body["max_length"] = serde_json::json!(max_tokens);
+        }
+        if let Some(num_ctx) = payload.num_ctx {
+            body["max_context_length"] = serde_json::json!(num_ctx);
+        }
+        if let Some(penalty) = payload.rep_pen {
+            body["rep_pen"] = serde_json::json!(penalty);
+        }
+        if let Some(repeat_last_n) = payload.repeat_last_n {
+            body["rep_pen_range"] = serde_json::json!(repeat_last_n);
+        }
+        if let Some(top_p) = payload.top_p {
+            body["top_p"] = serde_json::json!(top_p);
+        }
+        if let Some(top_k) = payload.top_k {
+            body["top_k"] = serde_json::json!(top_k);
+        }
+        if let Some(min_p) = payload.min_p {
+            body["min_p"] = serde_json::json!(min_p);
+        }
+
+        if let Some(multiplier) = payload.dry_multiplier {
+            body["dry_multiplier"] = serde_json::json!(multiplier);
+        }
+        if let Some(base) = payload.dry_base {
+            body["dry_base"] = serde_json::json!(base);
+        }
+        if let Some(allowed_length) = payload.dry_allowed_length {
+            body["dry_allowed_length"] = serde_json::json!(allowed_length);
+        }
+        if let Some(breakers) = payload.dry_sequence_breakers.as_ref() {
+            body["dry_sequence_breakers"] = serde_json::json!(breakers);
+        }
+        if let Some(threshold) = payload.xtc_threshold {
+            body["xtc_threshold"] = serde_json::json!(threshold);
+        }
+        if let Some(probability) = payload.xtc_probability {
+            body["xtc_probability"] = serde_json::json!(probability);
+        }
+        if let Some(mode) = payload.mirostat {
+            body["mirostat"] = serde_json::json!(mode);
+        }
+        if let Some(tau) = payload.mirostat_tau {
+            body["mirostat_tau"] = serde_json::json!(tau);
+        }
+        if let Some(eta) = payload.mirostat_eta {
+            body["mirostat_eta"] = serde_json::json!(eta);
+        }
+        if let Some(order) = payload.sampler_order.as_ref() {
+            body["sampler_order"] = serde_json::json!(order);
+        }
+
+        let mut req = CLIENT
+            .post(format!("{}/api/extra/generate/stream", native_base(&payload.url)))
+            .json(&body);
+
+        // Only set when KoboldCpp was started with --password.
+        if !payload.api_key.is_empty() {
+            req = req.bearer_auth(&payload.api_key);
+        }
+        Ok(req)
+    }
+
+    fn parse_event(&self, _event: &str, data: &str) -> Result<StreamDelta, String> {
+        let chunk: TokenChunk = serde_json::from_str(data).map_err(|e| e.to_string())?;
+        Ok(StreamDelta {
+            content: Some(chunk.token).filter(|t| !t.is_empty()),
+            done: chunk.finish_reason.is_some_and(|r| !r.is_empty()),
+            ..Default::default()
+        })
+    }
+
+    /// KoboldCpp keeps generating until max_length after the client disconnects,
+    /// blocking the next request, so a cancel has to be forwarded explicitly.
+    fn abort_request(&self, payload: &AiRequest) -> Option<reqwest::RequestBuilder> {
+        let mut req = CLIENT
+            .post(format!("{}/api/extra/abort", native_base(&payload.url)))
+            .json(&serde_json::json!({ "genkey": payload.generation_id }));
+        if !payload.api_key.is_empty() {
+            req = req.bearer_auth(&payload.api_key);
+        }
+        Some(req)
+    }
+
+    fn models_request(&self, url: &str, api_key: &str) -> reqwest::RequestBuilder {
+        let mut req = CLIENT.get(format!("{}/api/v1/model", native_base(url)));
+        if !api_key.is_empty() {
+            req = req.bearer_auth(api_key);
+        }
+        req
+    }
+
+    /// KoboldCpp serves exactly one model, reported as "koboldcpp/<name>".
+    fn parse_models(&self, body: &str) -> Result<Vec<String>, serde_json::Error> {
+        let parsed: ModelResponse = serde_json::from_str(body)?;
+        Ok(vec![parsed.result])
+    }
+}
@@ -0,0 +1,60 @@
+//! A bare HTTP server on a local port for tests that need a real response.
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::JoinHandle;
+
+/// Answers one connection per response, in order, each with the raw HTTP
+/// `responses[i]` (closed afterwards). Returns the base URL (with the `/v1`
+/// suffix the settings store) and a handle that yields the requests received
+/// once every response is sent.
+pub(crate) fn serve(responses: Vec<String>) -> (String, JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/v1", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        responses
+            .into_iter()
+            .map(|response| {
+                let (mut socket, _) = listener.accept().unwrap();
+                let request = read_request(&mut socket);
+                socket.write_all(response.as_bytes()).unwrap();
+                request
+            })
+            .collect()
+    });
+    (url, server)
+}
+
+/// A 200 response streaming `body` as server-sent events.
+pub(crate) fn sse(body: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n{}",
+        body
+    )
+}
+
+/// Reads the headers, then as much body as they announce.
+fn read_request(socket: &mut TcpStream) -> String {
+    let mut received = Vec::new();
+    let mut buf = [0; 4096];
+    loop {
+        let n = socket.read(&mut buf).unwrap();
+        received.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&received).to_lowercase();
+        let Some(end) = text.find("\r\n\r\n") else {
+            if n == 0 {
+                break;
+            }
+            continue;
+        };
+        let length = text[..end]
+            .lines()
+            .find_map(|l| l.strip_prefix("content-length:"))
+            .and_then(|l| l.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+        if n == 0 || received.len() >= end + 4 + length {
+            break;
+        }
+    }
+    String::from_utf8_lossy(&received).into_owned()
+}
@@ -0,0 +1,760 @@
+use futures::stream::StreamExt;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, Window};
+use tokio_util::sync::CancellationToken;
+
+use crate::database::world_info::timed_effects::{self, StartedEffects};
+use crate::prompt::PromptOptions;
+
+mod anthropic;
+pub mod background;
+pub mod character_memory;
+pub mod continuation;
+pub(crate) mod embeddings;
+mod error;
+pub mod impersonate;
+mod inspector;
+mod kobold;
+#[cfg(test)]
+mod mock_server;
+mod ollama;
+mod openai;
+mod provider;
+mod retry;
+mod stats;
+mod stream;
+pub mod swipes;
+mod templates;
+
+pub use error::AiError;
+pub use provider::Provider;
+pub use stats::GenerationStats;
+pub use templates::InstructTemplate;
+
+// Reusing a single HTTP client across the entire app lifecycle prevents connection
+// exhaustion and takes advantage of internal connection pooling.
+// connect_timeout only bounds how long we wait to establish the TCP/TLS connection -
+// it does NOT bound the overall response time, so long-running generations are unaffected.
+pub static CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(10))
+        .build()
+        .expect("failed to build reqwest client")
+});
+
+/// A generation that is currently streaming. Every call_ai_api invocation registers
+/// one of these under its own ID, so a rolling summary, a swipe and a normal reply can
+/// run side by side and each be stopped on its own.
+struct ActiveGeneration {
+    token: CancellationToken,
+    info: GenerationInfo,
+}
+
+/// Public view of an active generation, returned by list_active_generations().
+#[derive(Serialize, Clone)]
+pub struct GenerationInfo {
+    pub id: String,
+    pub model: String,
+    /// Free-form tag set by the caller ("chat", "summary", "swipe", ...).
+    pub label: Option<String>,
+    /// Unix timestamp in milliseconds.
+    pub started_at: u64,
+}
+
+// parking_lot::Mutex instead of std::sync::Mutex: no poisoning, so a panic on some
+// other thread while holding the lock can never propagate into this one via an
+// unwrap(), and lock() returns the guard directly (no Result to unwrap).
+static GENERATIONS: Lazy<Mutex<HashMap<String, ActiveGeneration>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Unregisters a generation when dropped, so every exit path of call_ai_api
+/// (finished, cancelled, or an early `?` return) cleans up after itself.
+struct GenerationGuard {
+    id: String,
+}
+
+impl Drop for GenerationGuard {
+    fn drop(&mut self) {
+        GENERATIONS.lock().remove(&self.id);
+    }
+}
+
+/// Adds a generation to the registry and hands back its cancellation token.
+/// IDs are chosen by the caller so the frontend can filter events before the
+/// invoke() call even returns; a duplicate ID is rejected rather than silently
+/// orphaning the stream that already owns it.
+fn register_generation(
+    id: &str,
+    model: &str,
+    label: Option<String>,
+) -> Result<(GenerationGuard, CancellationToken), AiError> {
+    let mut generations = GENERATIONS.lock();
+    if generations.contains_key(id) {
+        return Err(AiError::InvalidRequest {
+            message: format!("Generation {} is already running", id),
+        });
+    }
+
+    let token = CancellationToken::new();
+    let started_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default();
+
+    generations.insert(
+        id.to_string(),
+        ActiveGeneration {
+            token: token.clone(),
+            info: GenerationInfo {
+                id: id.to_string(),
+                model: model.to_string(),
+                label,
+                started_at,
+            },
+        },
+    );
+
+    Ok((GenerationGuard { id: id.to_string() }, token))
+}
+
+/// A registered generation whose prompt is ready (or that was stopped first).
+struct StartedGeneration {
+    id: String,
+    /// Hold until the generation is over; dropping it unregisters it.
+    guard: GenerationGuard,
+    token: CancellationToken,
+    /// The timed effects of the prompt's world info, see resolve_messages().
+    /// None if the generation was stopped before its prompt was ready.
+    timed: Option<StartedEffects>,
+}
+
+/// Called from the frontend to hard-stop a stream.
+/// Cancels the generation's token, which makes the running select! arm resolve and
+/// drops the underlying TCP connection. Without an ID every active generation is
+/// stopped (used when the window closes).
+#[tauri::command]
+pub fn stop_generation(generation_id: Option<String>) {
+    let generations = GENERATIONS.lock();
+    match generation_id {
+        Some(id) => {
+            if let Some(generation) = generations.get(&id) {
+                generation.token.cancel();
+            }
+        }
+        None => {
+            for generation in generations.values() {
+                generation.token.cancel();
+            }
+        }
+    }
+}
+
+/// Lists all generations that are currently streaming, oldest first.
+#[tauri::command]
+pub fn list_active_generations() -> Vec<GenerationInfo> {
+    let mut list: Vec<GenerationInfo> = GENERATIONS
+        .lock()
+        .values()
+        .map(|g| g.info.clone())
+        .collect();
+    list.sort_by_key(|info| info.started_at);
+    list
+}
+
+/// Payload received from the frontend to initiate an AI generation request.
+/// New fields carry a camelCase alias in addition to their snake_case name, since it's
+/// unclear which casing convention the caller uses for them yet — existing fields are
+/// left untouched to avoid breaking whatever already works.
+#[derive(Deserialize, Clone)]
+pub(crate) struct AiRequest {
+    // Wire protocol to use; omitted = OpenAI-compatible.
+    #[serde(default)]
+    provider: Provider,
+    url: String,
+    api_key: String,
+    model: String,
+    // May be left out when `chat_id` is set; see resolve_messages.
+    #[serde(default)]
+    messages: Vec<serde_json::Value>,
+    temperature: f32,
+    max_tokens: Option<u32>,
+    // NOTE: historically named `presence_penalty` but intentionally mapped below to the
+    // JSON key "repetition_penalty" — that's the llama.cpp/koboldcpp sampler local models
+    // (LM Studio included) actually understand and it's the more effective anti-repeat
+    // knob for roleplay. Kept as-is to avoid breaking existing saved settings; true
+    // OpenAI-style presence/frequency penalties are added separately below.
+    presence_penalty: Option<f32>,
+
+    // --- Additional roleplay-relevant sampling params ---
+    #[serde(alias = "topP")]
+    top_p: Option<f32>,
+    #[serde(alias = "topK")]
+    top_k: Option<u32>,
+    #[serde(alias = "minP")]
+    min_p: Option<f32>,
+    #[serde(alias = "frequencyPenalty")]
+    frequency_penalty: Option<f32>,
+    // KoboldCpp's repetition penalty. KoboldCpp has no presence or frequency
+    // penalty: presence_penalty isn't sent to it, frequency_penalty is rejected.
+    #[serde(alias = "repPen")]
+    rep_pen: Option<f32>,
+    // Number of choices to generate in one request; only sent where the provider
+    // supports it (see ProviderBackend::supports_n). Set by generate_swipes.
+    n: Option<u32>,
+
+    // --- Thinking / reasoning model support (Gemma 4, Qwen3, ...) ---
+    #[serde(alias = "isThinkingModel")]
+    is_thinking_model: bool,
+    // Token budget for the reasoning phase. Forwarded to llama.cpp's
+    // `thinking_budget_tokens` request field. 0 = end reasoning immediately,
+    // omitted/None = let the server default (usually unrestricted) apply.
+    #[serde(alias = "thinkingBudget")]
+    thinking_budget: Option<u32>,
+
+    // --- Native local-backend options (Ollama, KoboldCpp; ignored by the others) ---
+    // Context window size; Ollama's default of 2048 silently truncates long chats.
+    #[serde(alias = "numCtx")]
+    num_ctx: Option<u32>,
+    // How far back the repetition penalty looks. -1 = the whole context (Ollama).
+    #[serde(alias = "repeatLastN")]
+    repeat_last_n: Option<i32>,
+    // How long the model stays loaded after the request: a duration string ("10m")
+    // or seconds as a number, both passed through untouched.
+    #[serde(alias = "keepAlive")]
+    keep_alive: Option<serde_json::Value>,
+
+    // --- KoboldCpp samplers the OpenAI shim doesn't expose ---
+    // DRY ("don't repeat yourself") penalises continuing sequences already in context.
+    #[serde(alias = "dryMultiplier")]
+    dry_multiplier: Option<f32>,
+    #[serde(alias = "dryBase")]
+    dry_base: Option<f32>,
+    #[serde(alias = "dryAllowedLength")]
+    dry_allowed_length: Option<u32>,
+    #[serde(alias = "drySequenceBreakers")]
+    dry_sequence_breakers: Option<Vec<String>>,
+    // XTC ("exclude top choices") occasionally drops the most likely tokens.
+    #[serde(alias = "xtcThreshold")]
+    xtc_threshold: Option<f32>,
+    #[serde(alias = "xtcProbability")]
+    xtc_probability: Option<f32>,
+    // 0 = off, 1 = Mirostat, 2 = Mirostat 2.0.
+    mirostat: Option<u8>,
+    #[serde(alias = "mirostatTau")]
+    mirostat_tau: Option<f32>,
+    #[serde(alias = "mirostatEta")]
+    mirostat_eta: Option<f32>,
+    #[serde(alias = "samplerOrder")]
+    sampler_order: Option<Vec<u32>>,
+
+    // --- Text-completion mode ---
+    // Renders `messages` through an instruct template on our side and streams from
+    // /completions instead of /chat/completions. Only switches the OpenAI-compatible
+    // provider; KoboldCpp is text-completion anyway and just uses the template.
+    #[serde(default, alias = "textCompletion")]
+    text_completion: bool,
+    #[serde(alias = "instructTemplate")]
+    instruct_template: Option<InstructTemplate>,
+    // Jinja source (Hugging Face chat_template style), used with InstructTemplate::Custom.
+    #[serde(alias = "customTemplate")]
+    custom_template: Option<String>,
+
+    // --- Prompt built on the backend ---
+    // With a chat ID and no `messages`, the prompt is assembled from the
+    // database by prompt::assemble instead of being sent prebuilt.
+    #[serde(alias = "chatId")]
+    chat_id: Option<String>,
+    #[serde(alias = "promptOptions")]
+    prompt_options: Option<PromptOptions>,
+
+    // --- Continue ---
+    // Continue the trailing assistant message instead of answering it. Only set
+    // by continue_generation, which also makes sure that message is there.
+    #[serde(skip)]
+    continue_last: bool,
+
+    // --- Retry / backoff for failures before the first byte arrives ---
+    // Retries of rate-limited, overloaded or unreachable servers; 0 disables.
+    #[serde(alias = "maxRetries")]
+    max_retries: Option<u32>,
+    // First backoff step; doubled on every further attempt.
+    #[serde(alias = "retryBaseDelayMs")]
+    retry_base_delay_ms: Option<u64>,
+    // Upper bound for a single wait, also caps a server's Retry-After.
+    #[serde(alias = "retryMaxDelayMs")]
+    retry_max_delay_ms: Option<u64>,
+
+    // --- Generation tracking ---
+    // Chosen by the frontend so it can start filtering events by ID before the
+    // request is even sent. A fresh UUID is used when omitted.
+    #[serde(alias = "generationId")]
+    generation_id: Option<String>,
+    label: Option<String>,
+}
+
+impl AiRequest {
+    /// Options for a prompt built from `chat_id`, created empty if the caller
+    /// sent none, so commands can narrow the history (e.g. up to the message
+    /// being regenerated).
+    fn prompt_options_mut(&mut self) -> &mut PromptOptions {
+        self.prompt_options.get_or_insert_with(PromptOptions::default)
+    }
+
+    /// Registers the generation under `generation_id` (created if missing) and
+    /// then resolves the messages. Registered first, a generation can be listed
+    /// and stopped while its prompt is being assembled, which can take a while
+    /// with embedding requests on the way.
+    async fn start(&mut self, app: &AppHandle) -> Result<StartedGeneration, AiError> {
+        // Written back into the payload so providers that tag requests server-side
+        // (KoboldCpp's genkey) see the same ID.
+        let id = self
+            .generation_id
+            .get_or_insert_with(|| uuid::Uuid::new_v4().to_string())
+            .clone();
+        let (guard, token) = register_generation(&id, &self.model, self.label.clone())?;
+        let timed = tokio::select! {
+            biased;
+            _ = token.cancelled() => None,
+            timed = self.resolve_messages(app) => Some(timed?),
+        };
+        Ok(StartedGeneration { id, guard, token, timed })
+    }
+
+    /// Fills `messages` from the database if the caller sent a chat ID instead
+    /// of a prebuilt history. A prebuilt history always wins. Returns the timed
+    /// effects the prompt's world info starts, for the caller to record once
+    /// the reply is kept.
+    async fn resolve_messages(&mut self, app: &AppHandle) -> Result<StartedEffects, AiError> {
+        let Some(chat_id) = self.chat_id.as_deref().filter(|_| self.messages.is_empty()) else {
+            return Ok(StartedEffects::default());
+        };
+        let mut options = self.prompt_options.clone().unwrap_or_default();
+        options.model.get_or_insert_with(|| self.model.clone());
+        let prompt = crate::prompt::assemble(app, chat_id, &options)
+            .await
+            .map_err(|message| AiError::InvalidRequest { message })?;
+        self.messages = prompt
+            .messages
+            .into_iter()
+            .map(|m| serde_json::json!(m))
+            .collect();
+        Ok(prompt.started)
+    }
+}
+
+/// Payload emitted back to the frontend containing the generated text.
+/// Tagged with the generation ID so concurrent streams don't mix.
+#[derive(Serialize, Clone)]
+struct TokenPayload {
+    generation_id: String,
+    token: String,
+}
+
+/// Payload emitted back to the frontend containing reasoning/"thinking" text.
+#[derive(Serialize, Clone)]
+struct ThinkingTokenPayload {
+    generation_id: String,
+    token: String,
+}
+
+/// Event names a stream emits its text under. Generations whose output must
+/// never mix into a chat reply (e.g. impersonation) use their own.
+#[derive(Clone, Copy)]
+struct EventChannel {
+    token: &'static str,
+    thinking: &'static str,
+}
+
+const CHAT_CHANNEL: EventChannel = EventChannel {
+    token: "ai-token",
+    thinking: "ai-thinking-token",
+};
+
+/// Payload for a problem that didn't end the stream (e.g. one unparseable chunk).
+#[derive(Serialize, Clone)]
+struct StreamWarningPayload {
+    generation_id: String,
+    error: AiError,
+}
+
+/// Result of a finished call_ai_api invocation.
+#[derive(Serialize)]
+pub struct GenerationResult {
+    pub generation_id: String,
+    /// Token counts and timing. None if the generation was stopped before the
+    /// request got through.
+    pub stats: Option<GenerationStats>,
+    /// Reasoning text from the provider's dedicated reasoning channel, if any.
+    pub reasoning: Option<String>,
+    /// True if the stream was stopped via stop_generation().
+    pub cancelled: bool,
+}
+
+/// OpenAI-compatible /models response structs. Anthropic's /v1/models shares the shape.
+#[derive(Deserialize)]
+struct ModelsResponse {
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Deserialize)]
+struct ModelEntry {
+    id: String,
+}
+
+/// Fetches available models from the provider's model listing endpoint.
+/// Uses the shared CLIENT with the provider's auth (e.g. a Bearer token for OpenRouter).
+/// Running the HTTP call on the Rust side avoids CORS issues in the Tauri WebView.
+#[tauri::command]
+pub async fn fetch_models(
+    url: String,
+    api_key: String,
+    provider: Option<Provider>,
+) -> Result<Vec<String>, String> {
+    let backend = provider::backend(provider.unwrap_or_default());
+    let req = backend.models_request(&url, &api_key);
+
+    let res = req
+        .send()
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
+
+    if !res.status().is_success() {
+        return Err(format!("API error: Status {}", res.status()));
+    }
+
+    let body_text = res
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read response body: {}", e))?;
+
+    let ids = backend.parse_models(&body_text).map_err(|e| {
+        // chars().take(n) instead of byte-slicing: slicing a &str at an arbitrary byte
+        // index panics if that index falls inside a multi-byte UTF-8 character (e.g. an
+        // umlaut or emoji in the server's error message). This is char-boundary safe.
+        let preview: String = body_text.chars().take(500).collect();
+        format!("Failed to parse models response: {}. Raw body: {}", e, preview)
+    })?;
+
+    Ok(ids)
+}
+
+/// Emits whatever is currently buffered in either batch, then clears both buffers.
+/// Shared by the periodic flush tick and the final flush after the loop ends, so the
+/// emit logic only lives in one place.
+fn flush_batches(
+    window: &Window,
+    channel: EventChannel,
+    generation_id: &str,
+    token_batch: &mut String,
+    thinking_batch: &mut String,
+) -> Result<(), AiError> {
+    if !token_batch.is_empty() {
+        let batch = std::mem::take(token_batch);
+        window
+            .emit(channel.token, TokenPayload {
+                generation_id: generation_id.to_string(),
+                token: batch,
+            })?;
+    }
+    if !thinking_batch.is_empty() {
+        let batch = std::mem::take(thinking_batch);
+        window
+            .emit(channel.thinking, ThinkingTokenPayload {
+                generation_id: generation_id.to_string(),
+                token: batch,
+            })?;
+    }
+    Ok(())
+}
+
+/// Forwards a cancel to the server for providers that keep generating after the
+/// connection is dropped. Best effort: a failure here only means the server
+/// finishes the reply on its own, so it's logged rather than returned.
+async fn abort_on_server(backend: &dyn provider::ProviderBackend, payload: &AiRequest) {
+    let Some(req) = backend.abort_request(payload) else {
+        return;
+    };
+    if let Err(e) = req.timeout(Duration::from_secs(5)).send().await {
+        eprintln!("Failed to abort generation on the server: {}", e);
+    }
+}
+
+/// How often a stream hands its accumulated reply to the progress callback.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(750);
+
+/// What one choice of a stream produced.
+#[derive(Default)]
+struct ChoiceOutcome {
+    reply: String,
+    reasoning: String,
+    /// None if the generation was stopped before the request got through.
+    stats: Option<GenerationStats>,
+}
+
+/// Everything a stream produced, whether it ran to the end or not.
+struct StreamOutcome {
+    /// One entry per requested choice, in choice order.
+    choices: Vec<ChoiceOutcome>,
+    cancelled: bool,
+    /// Set if the stream broke off; the choices still hold what arrived before that.
+    error: Option<AiError>,
+}
+
+/// Streaming state of one choice. A normal request has a single one; a request
+/// with `n` > 1 has one per choice, each emitting under its own ID.
+struct ChoiceStream<'a> {
+    channel: EventChannel,
+    event_id: &'a str,
+    token_batch: String,
+    thinking_batch: String,
+    // Full reply and reasoning text: both are returned for persisting, and used to
+    // estimate the token count when the server reports no usage.
+    reply: String,
+    reasoning: String,
+    recorder: stats::StatsRecorder,
+}
+
+impl ChoiceStream<'_> {
+    fn flush(&mut self, window: &Window) -> Result<(), AiError> {
+        flush_batches(
+            window,
+            self.channel,
+            self.event_id,
+            &mut self.token_batch,
+            &mut self.thinking_batch,
+        )
+    }
+}
+
+/// Streams completions from the configured provider (OpenAI-compatible by default).
+/// Batches incoming tokens before emitting them to the frontend to prevent
+/// overwhelming the Tauri IPC bridge and freezing the Svelte UI.
+/// Uses the generation's CancellationToken so stop_generation(id) drops the TCP
+/// connection immediately - including during the initial connect, not just once
+/// streaming has started.
+/// `choice_ids` holds the ID each choice's tokens are emitted under on `channel`:
+/// just the generation ID normally, one per choice when `n` > 1. `on_progress` gets a
+/// choice's index and full reply so far every PROGRESS_INTERVAL and once more
+/// at the end.
+/// Failures before anything streamed are returned as Err; a stream that breaks
+/// off later comes back as Ok with `error` set, so the partial reply survives.
+async fn stream_generation(
+    window: &Window,
+    payload: &AiRequest,
+    generation_id: &str,
+    channel: EventChannel,
+    choice_ids: &[String],
+    token: &CancellationToken,
+    on_progress: &mut (dyn FnMut(usize, &str) + Send),
+) -> Result<StreamOutcome, AiError> {
+    let backend = provider::backend_for(payload);
+    // Started before sending, so time-to-first-token includes connecting and any
+    // retry waits — that's the delay the user actually sees.
+    let mut choices: Vec<ChoiceStream> = choice_ids
+        .iter()
+        .map(|id| ChoiceStream {
+            channel,
+            event_id: id,
+            token_batch: String::new(),
+            thinking_batch: String::new(),
+            reply: String::new(),
+            reasoning: String::new(),
+            recorder: stats::StatsRecorder::start(),
+        })
+        .collect();
+
+    inspector::record(window, backend, payload, choice_ids);
+
+    // Cancel-aware connect (and backoff): without this, stop_generation() would do
+    // nothing until the first byte arrives. A server that's slow/unreachable would
+    // hang here with no way to abort.
+    let res = match retry::send_with_retry(window, generation_id, backend, payload, token).await {
+        Ok(res) => res,
+        Err(AiError::Cancelled) => {
+            abort_on_server(backend, payload).await;
+            return Ok(StreamOutcome {
+                choices: choice_ids.iter().map(|_| ChoiceOutcome::default()).collect(),
+                cancelled: true,
+                error: None,
+            });
+        }
+        Err(e) => return Err(e),
+    };
+
+    let mut stream = stream::events(res, backend.stream_format());
+
+    let batch_delay = Duration::from_millis(25);
+    let mut last_progress = tokio::time::Instant::now();
+
+    // Ticks independently of incoming events, so a batch never sits unflushed just
+    // because the server paused between chunks (which the old "flush on next event,
+    // if enough time has passed" logic was prone to under bursty/uneven streaming).
+    let mut flush_tick = tokio::time::interval(batch_delay);
+    flush_tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    // Run as its own block so every way out of the loop, errors included, still
+    // reaches the final flush and progress report below.
+    let streamed: Result<(), AiError> = async {
+        loop {
+            tokio::select! {
+                // Checked first so a Stop click is never left waiting behind a pending
+                // event or tick that happens to be ready at the same instant.
+                biased;
+
+                _ = token.cancelled() => break,
+
+                maybe_event = stream.next() => {
+                    let Some(event_result) = maybe_event else {
+                        break;
+                    };
+
+                    match event_result {
+                        Ok(event) => match backend.parse_event(&event.event, &event.data) {
+                            Ok(delta) => {
+                                // A server that ignores `n` only ever reports choice 0,
+                                // and one that miscounts can't index out of bounds.
+                                if let Some(choice) = choices.get_mut(delta.choice) {
+                                    if let Some(content) = delta.content.as_ref() {
+                                        choice.recorder.token_received();
+                                        choice.token_batch.push_str(content);
+                                        choice.reply.push_str(content);
+                                    }
+                                    if let Some(text) = delta.reasoning.as_ref() {
+                                        choice.recorder.token_received();
+                                        choice.thinking_batch.push_str(text);
+                                        choice.reasoning.push_str(text);
+                                    }
+                                }
+                                if let Some(usage) = delta.usage {
+                                    if let [only] = choices.as_mut_slice() {
+                                        only.recorder.add_usage(usage);
+                                    } else {
+                                        // With several choices the completion count is a
+                                        // total, so each choice estimates its own instead.
+                                        for choice in choices.iter_mut() {
+                                            choice.recorder.add_usage(usage.prompt_only());
+                                        }
+                                    }
+                                }
+                                if let Some(error) = delta.error {
+                                    return Err(error);
+                                }
+                                if delta.done {
+                                    break;
+                                }
+                            }
+                            Err(e) => {
+                                // One bad chunk shouldn't throw away the whole reply, so
+                                // it's reported as a warning and the stream carries on.
+                                let preview: String = event.data.chars().take(200).collect();
+                                window.emit("ai-stream-warning", StreamWarningPayload {
+                                    generation_id: generation_id.to_string(),
+                                    error: AiError::MalformedStream {
+                                        message: format!("{} (raw: {})", e, preview),
+                                    },
+                                })?;
+                            }
+                        },
+                        Err(error) => return Err(error),
+                    }
+                }
+
+                _ = flush_tick.tick() => {
+                    for choice in choices.iter_mut() {
+                        choice.flush(window)?;
+                    }
+                    if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                        for (index, choice) in choices.iter().enumerate() {
+                            on_progress(index, &choice.reply);
+                        }
+                        last_progress = tokio::time::Instant::now();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    .await;
+
+    // Flush whatever was buffered when the stream stopped (DONE, cancel, error or
+    // close), so text that arrived before a failure is still delivered. The
+    // stream's own error, if any, is the one worth reporting.
+    let mut flushed = Ok(());
+    for (index, choice) in choices.iter_mut().enumerate() {
+        flushed = flushed.and(choice.flush(window));
+        on_progress(index, &choice.reply);
+    }
+
+    let cancelled = token.is_cancelled();
+    if cancelled {
+        abort_on_server(backend, payload).await;
+    }
+
+    Ok(StreamOutcome {
+        choices: choices
+            .into_iter()
+            .map(|choice| {
+                let generated = format!("{}{}", choice.reply, choice.reasoning);
+                ChoiceOutcome {
+                    stats: Some(choice.recorder.finish(&payload.model, &generated)),
+                    reply: choice.reply,
+                    reasoning: choice.reasoning,
+                }
+            })
+            .collect(),
+        cancelled,
+        error: streamed.and(flushed).err(),
+    })
+}
+
+/// Streams a reply to the frontend via "ai-token" / "ai-thinking-token" events.
+/// Returns the generation ID and the response's token usage and timing once the
+/// stream has ended; a stop is not a failure and returns Ok as well. Failures
+/// come back as a structured AiError.
+#[tauri::command]
+pub async fn call_ai_api(window: Window, mut payload: AiRequest) -> Result<GenerationResult, AiError> {
+    // Held until the end of this function; dropping it unregisters the generation.
+    let StartedGeneration { id: generation_id, guard: _guard, token, timed } =
+        payload.start(window.app_handle()).await?;
+    let Some(started) = timed else {
+        return Ok(GenerationResult {
+            generation_id,
+            stats: None,
+            reasoning: None,
+            cancelled: true,
+        });
+    };
+
+    let choice_ids = [generation_id.clone()];
+    let outcome = stream_generation(
+        &window,
+        &payload,
+        &generation_id,
+        CHAT_CHANNEL,
+        &choice_ids,
+        &token,
+        &mut |_, _| {},
+    )
+    .await?;
+    if let Some(error) = outcome.error {
+        return Err(error);
+    }
+    let choice = outcome.choices.into_iter().next().unwrap_or_default();
+    // The frontend saves the reply itself; a finished one with text is kept.
+    if !outcome.cancelled && !choice.reply.is_empty() {
+        if let Err(e) = timed_effects::record_started(window.app_handle(), &started) {
+            eprintln!("Failed to record world info timed effects: {}", e);
+        }
+    }
+
+    Ok(GenerationResult {
+        generation_id,
+        stats: choice.stats,
+        reasoning: Some(choice.reasoning).filter(|r| !r.is_empty()),
+        cancelled: outcome.cancelled,
+    })
+}
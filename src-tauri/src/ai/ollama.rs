@@ -0,0 +1,150 @@
+use serde::Deserialize;
+
+use super::error::AiError;
+use super::provider::{native_base, ProviderBackend, StreamDelta};
+use super::stats::Usage;
+use super::stream::StreamFormat;
+use super::{AiRequest, CLIENT};
+
+/// Ollama's native `/api/chat` endpoint. Unlike its OpenAI shim this accepts the
+/// full `options` object (num_ctx, repeat_last_n, ...) and `keep_alive`.
+pub(crate) struct Ollama;
+
+/// One NDJSON line of a streaming /api/chat response. The counters and
+/// durations (in nanoseconds) only appear on the final `done` line.
+#[derive(Deserialize)]
+struct ChatChunk {
+    message: Option<ChunkMessage>,
+    #[serde(default)]
+    done: bool,
+    error: Option<String>,
+    prompt_eval_count: Option<u32>,
+    prompt_eval_duration: Option<u64>,
+    eval_count: Option<u32>,
+    eval_duration: Option<u64>,
+}
+
+/// Tokens per second from an Ollama count + nanosecond duration pair.
+fn rate(count: Option<u32>, duration_ns: Option<u64>) -> Option<f64> {
+    match (count, duration_ns) {
+        (Some(count), Some(ns)) if ns > 0 => Some(count as f64 / (ns as f64 / 1e9)),
+        _ => None,
+    }
+}
+
+#[derive(Deserialize)]
+struct ChunkMessage {
+    content: Option<String>,
+    thinking: Option<String>,
+}
+
+/// /api/tags response structs.
+#[derive(Deserialize)]
+struct TagsResponse {
+    models: Vec<TagEntry>,
+}
+
+#[derive(Deserialize)]
+struct TagEntry {
+    name: String,
+}
+
+impl ProviderBackend for Ollama {
+    fn build_request(&self, payload: &AiRequest) -> Result<reqwest::RequestBuilder, String> {
+        let mut options = serde_json::json!({
+            "temperature": payload.temperature,
+        });
+
+        if let Some(max_tokens) = payload.max_tokens {
+            options["num_predict"] = serde_json::json!(max_tokens);
+        }
+        // Same llama.cpp repetition sampler the OpenAI path maps this to —
+        // see the doc comment on AiRequest::presence_penalty.
+        if let Some(penalty) = payload.presence_penalty {
+            options["repeat_penalty"] = serde_json::json!(penalty);
+        }
+        if let Some(top_p) = payload.top_p {
+            options["top_p"] = serde_json::json!(top_p);
+        }
+        if let Some(top_k) = payload.top_k {
+            options["top_k"] = serde_json::json!(top_k);
+        }
+        if let Some(min_p) = payload.min_p {
+            options["min_p"] = serde_json::json!(min_p);
+        }
+        if let Some(freq_penalty) = payload.frequency_penalty {
+            options["frequency_penalty"] = serde_json::json!(freq_penalty);
+        }
+        if let Some(num_ctx) = payload.num_ctx {
+            options["num_ctx"] = serde_json::json!(num_ctx);
+        }
+        if let Some(repeat_last_n) = payload.repeat_last_n {
+            options["repeat_last_n"] = serde_json::json!(repeat_last_n);
+        }
+
+        let mut body = serde_json::json!({
+            "model": payload.model,
+            "messages": payload.messages,
+            "stream": true,
+            "options": options,
+            // Reasoning arrives separately in message.thinking when enabled.
+            "think": payload.is_thinking_model,
+        });
+
+        if let Some(keep_alive) = payload.keep_alive.as_ref() {
+            body["keep_alive"] = keep_alive.clone();
+        }
+
+        let mut req = CLIENT
+            .post(format!("{}/api/chat", native_base(&payload.url)))
+            .json(&body);
+
+        // Ollama itself has no auth, but it's often put behind a reverse proxy that does.
+        if !payload.api_key.is_empty() {
+            req = req.bearer_auth(&payload.api_key);
+        }
+        Ok(req)
+    }
+
+    fn stream_format(&self) -> StreamFormat {
+        StreamFormat::Ndjson
+    }
+
+    fn parse_event(&self, _event: &str, data: &str) -> Result<StreamDelta, String> {
+        let chunk: ChatChunk = serde_json::from_str(data).map_err(|e| e.to_string())?;
+        let mut delta = StreamDelta {
+            done: chunk.done,
+            ..Default::default()
+        };
+        if chunk.done {
+            delta.usage = Some(Usage {
+                prompt_tokens: chunk.prompt_eval_count,
+                completion_tokens: chunk.eval_count,
+                tokens_per_second: rate(chunk.eval_count, chunk.eval_duration),
+                prompt_tokens_per_second: rate(chunk.prompt_eval_count, chunk.prompt_eval_duration),
+            });
+        }
+        if let Some(message) = chunk.message {
+            delta.content = message.content.filter(|c| !c.is_empty());
+            delta.reasoning = message.thinking.filter(|t| !t.is_empty());
+        }
+        if let Some(message) = chunk.error {
+            delta.error = Some(AiError::Api { status: None, message });
+            delta.done = true;
+        }
+        Ok(delta)
+    }
+
+    fn models_request(&self, url: &str, api_key: &str) -> reqwest::RequestBuilder {
+        let mut req = CLIENT.get(format!("{}/api/tags", native_base(url)));
+        if !api_key.is_empty() {
+            req = req.bearer_auth(api_key);
+        }
+        req
+    }
+
+    fn parse_models(&self, body: &str) -> Result<Vec<String>, serde_json::Error> {
+        let parsed: TagsResponse = serde_json::from_str(body)?;
+        Ok(parsed.models.into_iter().map(|m| m.name).collect())
+    }
+}
@@ -0,0 +1,225 @@
+use serde::Deserialize;
+
+use super::provider::{ProviderBackend, StreamDelta};
+use super::stats::Usage;
+use super::templates::render_prompt;
+use super::{AiRequest, CLIENT};
+
+/// OpenAI-compatible `/chat/completions` dialect. Also what LM Studio, llama.cpp,
+/// KoboldCpp's shim and OpenRouter speak.
+pub(crate) struct OpenAi;
+
+/// OpenAI-compatible legacy `/completions` endpoint, fed a prompt rendered through
+/// an instruct template on our side instead of the server's chat template.
+pub(crate) struct OpenAiText;
+
+/// OpenAI-compatible SSE (Server-Sent Events) streaming structs.
+/// The final chunk requested via stream_options.include_usage has an empty
+/// `choices` array and only carries `usage`.
+#[derive(Deserialize)]
+struct StreamChunk {
+    #[serde(default)]
+    choices: Vec<Choice>,
+    usage: Option<ApiUsage>,
+    timings: Option<Timings>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    #[serde(default)]
+    index: usize,
+    delta: Delta,
+}
+
+#[derive(Deserialize)]
+struct Delta {
+    content: Option<String>,
+    reasoning_content: Option<String>,
+}
+
+/// Text-completion SSE chunk: same envelope, but `text` instead of a delta.
+#[derive(Deserialize)]
+struct TextChunk {
+    #[serde(default)]
+    choices: Vec<TextChoice>,
+    usage: Option<ApiUsage>,
+    timings: Option<Timings>,
+}
+
+#[derive(Deserialize)]
+struct TextChoice {
+    #[serde(default)]
+    index: usize,
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ApiUsage {
+    prompt_tokens: Option<u32>,
+    completion_tokens: Option<u32>,
+}
+
+/// llama.cpp's own performance report, sent alongside the last chunk.
+#[derive(Deserialize)]
+struct Timings {
+    prompt_per_second: Option<f64>,
+    predicted_per_second: Option<f64>,
+}
+
+fn usage_of(usage: Option<ApiUsage>, timings: Option<Timings>) -> Option<Usage> {
+    if usage.is_none() && timings.is_none() {
+        return None;
+    }
+    let mut merged = Usage::default();
+    if let Some(usage) = usage {
+        merged.prompt_tokens = usage.prompt_tokens;
+        merged.completion_tokens = usage.completion_tokens;
+    }
+    if let Some(timings) = timings {
+        merged.tokens_per_second = timings.predicted_per_second;
+        merged.prompt_tokens_per_second = timings.prompt_per_second;
+    }
+    Some(merged)
+}
+
+/// Sampler settings shared by both endpoints.
+fn apply_samplers(body: &mut serde_json::Value, payload: &AiRequest) {
+    // Asks for a final usage chunk. Servers that don't know the option ignore it.
+    body["stream_options"] = serde_json::json!({ "include_usage": true });
+
+    if let Some(max_tokens) = payload.max_tokens {
+        body["max_tokens"] = serde_json::json!(max_tokens);
+    }
+    // Mapped to "repetition_penalty" (llama.cpp/koboldcpp sampler), not the OpenAI
+    // "presence_penalty" semantics — see the doc comment on AiRequest::presence_penalty.
+    if let Some(penalty) = payload.presence_penalty {
+        body["repetition_penalty"] = serde_json::json!(penalty);
+    }
+    if let Some(top_p) = payload.top_p {
+        body["top_p"] = serde_json::json!(top_p);
+    }
+    if let Some(top_k) = payload.top_k {
+        body["top_k"] = serde_json::json!(top_k);
+    }
+    if let Some(min_p) = payload.min_p {
+        body["min_p"] = serde_json::json!(min_p);
+    }
+    if let Some(freq_penalty) = payload.frequency_penalty {
+        body["frequency_penalty"] = serde_json::json!(freq_penalty);
+    }
+    if let Some(n) = payload.n.filter(|n| *n > 1) {
+        body["n"] = serde_json::json!(n);
+    }
+}
+
+fn post(payload: &AiRequest, path: &str, body: &serde_json::Value) -> reqwest::RequestBuilder {
+    let mut req = CLIENT
+        .post(format!("{}{}", payload.url, path))
+        .json(body);
+
+    if !payload.api_key.is_empty() {
+        req = req.bearer_auth(&payload.api_key);
+    }
+    req
+}
+
+impl ProviderBackend for OpenAi {
+    fn build_request(&self, payload: &AiRequest) -> Result<reqwest::RequestBuilder, String> {
+        let mut body = serde_json::json!({
+            "model": payload.model,
+            "messages": payload.messages,
+            "temperature": payload.temperature,
+            "stream": true
+        });
+
+        apply_samplers(&mut body, payload);
+
+        // Thinking / reasoning models (Gemma 4, Qwen3, ...) are toggled per-request via
+        // llama.cpp's chat_template_kwargs. Sending this even when disabled is intentional,
+        // so switching a character/model between thinking and non-thinking mid-session
+        // doesn't leak the previous request's state.
+        body["chat_template_kwargs"] = serde_json::json!({ "enable_thinking": payload.is_thinking_model });
+        if payload.is_thinking_model {
+            if let Some(budget) = payload.thinking_budget {
+                // 0 = end reasoning immediately, N>0 = token budget, omit for server default (usually unrestricted).
+                body["thinking_budget_tokens"] = serde_json::json!(budget);
+            }
+        }
+
+        if payload.continue_last {
+            // vLLM needs to be told explicitly; llama.cpp detects the trailing
+            // assistant turn on its own and ignores these.
+            body["continue_final_message"] = serde_json::json!(true);
+            body["add_generation_prompt"] = serde_json::json!(false);
+        }
+
+        Ok(post(payload, "/chat/completions", &body))
+    }
+
+    fn parse_event(&self, _event: &str, data: &str) -> Result<StreamDelta, String> {
+        if data == "[DONE]" {
+            return Ok(StreamDelta { done: true, ..Default::default() });
+        }
+
+        let chunk: StreamChunk = serde_json::from_str(data).map_err(|e| e.to_string())?;
+        let mut delta = StreamDelta {
+            usage: usage_of(chunk.usage, chunk.timings),
+            ..Default::default()
+        };
+        // With n > 1 each chunk still carries a single choice, tagged by its index.
+        if let Some(choice) = chunk.choices.into_iter().next() {
+            delta.choice = choice.index;
+            delta.content = choice.delta.content;
+            delta.reasoning = choice.delta.reasoning_content;
+        }
+        Ok(delta)
+    }
+
+    fn supports_n(&self) -> bool {
+        true
+    }
+}
+
+impl ProviderBackend for OpenAiText {
+    fn build_request(&self, payload: &AiRequest) -> Result<reqwest::RequestBuilder, String> {
+        let rendered = render_prompt(
+            payload.instruct_template.unwrap_or_default(),
+            payload.custom_template.as_deref(),
+            &payload.messages,
+            payload.continue_last,
+        )?;
+
+        let mut body = serde_json::json!({
+            "model": payload.model,
+            "prompt": rendered.prompt,
+            "temperature": payload.temperature,
+            "stop": rendered.stop,
+            "stream": true
+        });
+
+        apply_samplers(&mut body, payload);
+
+        Ok(post(payload, "/completions", &body))
+    }
+
+    fn parse_event(&self, _event: &str, data: &str) -> Result<StreamDelta, String> {
+        if data == "[DONE]" {
+            return Ok(StreamDelta { done: true, ..Default::default() });
+        }
+
+        let chunk: TextChunk = serde_json::from_str(data).map_err(|e| e.to_string())?;
+        let mut delta = StreamDelta {
+            usage: usage_of(chunk.usage, chunk.timings),
+            ..Default::default()
+        };
+        if let Some(choice) = chunk.choices.into_iter().next() {
+            delta.choice = choice.index;
+            delta.content = choice.text;
+        }
+        Ok(delta)
+    }
+
+    fn supports_n(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+
+use super::error::AiError;
+use super::stats::Usage;
+use super::stream::StreamFormat;
+use super::{
+    anthropic::Anthropic, kobold::KoboldCpp, ollama::Ollama, openai::OpenAi, openai::OpenAiText,
+    AiRequest, ModelsResponse, CLIENT,
+};
+
+/// Which wire protocol a request is sent with. Defaults to the OpenAI-compatible
+/// dialect, which is what every existing saved setting was written for.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    #[default]
+    #[serde(alias = "openAi", alias = "open_ai")]
+    OpenAi,
+    Anthropic,
+    Ollama,
+    #[serde(alias = "koboldCpp", alias = "kobold")]
+    KoboldCpp,
+}
+
+/// What a provider extracted from a single stream event.
+/// Everything is optional because most events only carry one of these (or nothing
+/// at all, like Anthropic's `ping`).
+#[derive(Default)]
+pub(crate) struct StreamDelta {
+    pub content: Option<String>,
+    pub reasoning: Option<String>,
+    /// The provider signalled the end of the response.
+    pub done: bool,
+    /// The provider reported an error in-band, after the HTTP status was already 200.
+    pub error: Option<AiError>,
+    /// Token counts / timings, usually only on the final event.
+    pub usage: Option<Usage>,
+    /// Which of several requested choices (`n` > 1) this belongs to; 0 otherwise.
+    pub choice: usize,
+}
+
+/// One wire protocol. Implementations only translate between AiRequest and the
+/// provider's JSON - connecting, batching, emitting and cancelling live in
+/// call_ai_api and are shared by all of them.
+pub(crate) trait ProviderBackend: Sync {
+    /// Builds the streaming request: URL, auth headers and body.
+    /// Err if the payload can't be expressed in this protocol (e.g. a broken template).
+    fn build_request(&self, payload: &AiRequest) -> Result<reqwest::RequestBuilder, String>;
+
+    /// How the streaming response is framed.
+    fn stream_format(&self) -> StreamFormat {
+        StreamFormat::Sse
+    }
+
+    /// Parses one stream event. `event` is the SSE event name, empty if the server
+    /// didn't send one. Err is a malformed chunk, which the caller logs and skips.
+    fn parse_event(&self, event: &str, data: &str) -> Result<StreamDelta, String>;
+
+    /// Whether the protocol can return several choices for one request (`n`).
+    /// Without it, generating several swipes falls back to parallel requests.
+    fn supports_n(&self) -> bool {
+        false
+    }
+
+    /// Builds the request that tells the server to stop generating after a cancel.
+    /// Only needed where dropping the connection isn't enough on its own.
+    fn abort_request(&self, _payload: &AiRequest) -> Option<reqwest::RequestBuilder> {
+        None
+    }
+
+    /// Builds the request for listing available models.
+    fn models_request(&self, url: &str, api_key: &str) -> reqwest::RequestBuilder {
+        let mut req = CLIENT.get(format!("{}/models", url));
+        if !api_key.is_empty() {
+            req = req.bearer_auth(api_key);
+        }
+        req
+    }
+
+    /// Extracts model IDs from the models response body.
+    fn parse_models(&self, body: &str) -> Result<Vec<String>, serde_json::Error> {
+        let parsed: ModelsResponse = serde_json::from_str(body)?;
+        Ok(parsed.data.into_iter().map(|m| m.id).collect())
+    }
+}
+
+/// Resolves a provider to its (stateless) implementation.
+pub(crate) fn backend(provider: Provider) -> &'static dyn ProviderBackend {
+    match provider {
+        Provider::OpenAi => &OpenAi,
+        Provider::Anthropic => &Anthropic,
+        Provider::Ollama => &Ollama,
+        Provider::KoboldCpp => &KoboldCpp,
+    }
+}
+
+/// Resolves the implementation for one request. The OpenAI-compatible provider
+/// switches to /completions when text-completion mode is on.
+pub(crate) fn backend_for(payload: &AiRequest) -> &'static dyn ProviderBackend {
+    match payload.provider {
+        Provider::OpenAi if payload.text_completion => &OpenAiText,
+        provider => backend(provider),
+    }
+}
+
+/// The settings page stores local backends with the `/v1` suffix of their OpenAI
+/// shim (e.g. http://127.0.0.1:11434/v1). Native APIs live at the root, so strip it.
+pub(crate) fn native_base(url: &str) -> &str {
+    let url = url.trim_end_matches('/');
+    url.strip_suffix("/v1").unwrap_or(url)
+}
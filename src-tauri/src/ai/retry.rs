@@ -0,0 +1,230 @@
+use serde::Serialize;
+use std::time::Duration;
+use tauri::{Emitter, Window};
+use tokio_util::sync::CancellationToken;
+
+use super::error::AiError;
+use super::provider::ProviderBackend;
+use super::AiRequest;
+
+const DEFAULT_MAX_RETRIES: u32 = 2;
+const DEFAULT_BASE_DELAY_MS: u64 = 1000;
+const DEFAULT_MAX_DELAY_MS: u64 = 30_000;
+
+/// Exponential backoff settings, taken from the request with defaults filled in.
+struct RetryPolicy {
+    max_retries: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+impl RetryPolicy {
+    fn from_request(payload: &AiRequest) -> Self {
+        RetryPolicy {
+            max_retries: payload.max_retries.unwrap_or(DEFAULT_MAX_RETRIES),
+            base_delay: Duration::from_millis(payload.retry_base_delay_ms.unwrap_or(DEFAULT_BASE_DELAY_MS)),
+            max_delay: Duration::from_millis(payload.retry_max_delay_ms.unwrap_or(DEFAULT_MAX_DELAY_MS)),
+        }
+    }
+
+    /// base * 2^attempt, unless the server named its own wait. Both are capped so
+    /// a huge Retry-After can't park the generation for an hour.
+    fn delay(&self, attempt: u32, error: &AiError) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt));
+        error.retry_after().unwrap_or(backoff).min(self.max_delay)
+    }
+
+    /// How long to wait before retrying after `attempt` earlier retries failed
+    /// with `error`; None if it isn't worth another try.
+    fn next_delay(&self, attempt: u32, error: &AiError) -> Option<Duration> {
+        (error.is_retryable() && attempt < self.max_retries).then(|| self.delay(attempt, error))
+    }
+}
+
+/// Emitted before each retry so the UI can show "retrying in Ns".
+#[derive(Serialize, Clone)]
+struct RetryPayload {
+    generation_id: String,
+    /// 1-based number of the retry about to happen.
+    attempt: u32,
+    max_retries: u32,
+    delay_ms: u64,
+    error: AiError,
+}
+
+/// Sends the request and waits for a successful response status, retrying
+/// retryable failures (rate limits, overload, network) with exponential backoff.
+/// Only covers the time before the first byte of the body arrives; once
+/// streaming starts nothing is retried, so a reply can never be generated twice.
+/// Both the request and the backoff waits abort with AiError::Cancelled on stop.
+pub(crate) async fn send_with_retry(
+    window: &Window,
+    generation_id: &str,
+    backend: &dyn ProviderBackend,
+    payload: &AiRequest,
+    token: &CancellationToken,
+) -> Result<reqwest::Response, AiError> {
+    let policy = RetryPolicy::from_request(payload);
+    let mut on_retry = |attempt: u32, delay: Duration, error: AiError| {
+        window.emit("ai-retry", RetryPayload {
+            generation_id: generation_id.to_string(),
+            attempt,
+            max_retries: policy.max_retries,
+            delay_ms: delay.as_millis() as u64,
+            error,
+        })?;
+        Ok(())
+    };
+    send(&policy, backend, payload, token, &mut on_retry).await
+}
+
+/// send_with_retry() without the window: `on_retry` gets the 1-based number of
+/// each retry, its wait and the error that caused it.
+async fn send(
+    policy: &RetryPolicy,
+    backend: &dyn ProviderBackend,
+    payload: &AiRequest,
+    token: &CancellationToken,
+    on_retry: &mut (dyn FnMut(u32, Duration, AiError) -> Result<(), AiError> + Send),
+) -> Result<reqwest::Response, AiError> {
+    let mut attempt = 0;
+
+    loop {
+        // Rebuilt per attempt: a RequestBuilder is consumed by send().
+        let req = backend
+            .build_request(payload)
+            .map_err(|message| AiError::InvalidRequest { message })?;
+
+        let result = tokio::select! {
+            result = req.send() => result,
+            _ = token.cancelled() => return Err(AiError::Cancelled),
+        };
+
+        let error = match result {
+            Ok(res) if res.status().is_success() => return Ok(res),
+            Ok(res) => AiError::from_response(res).await,
+            Err(e) => AiError::from(e),
+        };
+
+        let Some(delay) = policy.next_delay(attempt, &error) else {
+            return Err(error);
+        };
+        attempt += 1;
+        on_retry(attempt, delay, error)?;
+
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = token.cancelled() => return Err(AiError::Cancelled),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::mock_server;
+    use crate::ai::provider;
+
+    fn policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            base_delay: Duration::from_millis(1000),
+            max_delay: Duration::from_millis(30_000),
+        }
+    }
+
+    fn overloaded() -> AiError {
+        AiError::ServerOverloaded { message: String::new() }
+    }
+
+    #[test]
+    fn backs_off_exponentially_up_to_the_cap() {
+        let policy = policy(10);
+        assert_eq!(policy.delay(0, &overloaded()), Duration::from_secs(1));
+        assert_eq!(policy.delay(3, &overloaded()), Duration::from_secs(8));
+        assert_eq!(policy.delay(9, &overloaded()), Duration::from_secs(30));
+        // Far past any real attempt count, without overflowing.
+        assert_eq!(policy.delay(40, &overloaded()), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn prefers_the_servers_wait_within_the_cap() {
+        let policy = policy(10);
+        let limited = |secs| AiError::RateLimited { retry_after_secs: Some(secs), message: String::new() };
+        assert_eq!(policy.delay(3, &limited(2)), Duration::from_secs(2));
+        assert_eq!(policy.delay(0, &limited(3600)), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn stops_retrying_after_the_limit_or_on_a_permanent_error() {
+        let policy = policy(2);
+        assert!(policy.next_delay(0, &overloaded()).is_some());
+        assert!(policy.next_delay(1, &overloaded()).is_some());
+        assert!(policy.next_delay(2, &overloaded()).is_none());
+        assert!(policy.next_delay(0, &AiError::Auth { message: String::new() }).is_none());
+        assert!(policy.next_delay(0, &AiError::ContextOverflow { message: String::new() }).is_none());
+    }
+
+    fn request(url: &str) -> AiRequest {
+        serde_json::from_value(serde_json::json!({
+            "provider": "openai",
+            "url": url,
+            "api_key": "",
+            "model": "local",
+            "messages": [{ "role": "user", "content": "Hi" }],
+            "temperature": 1.0,
+            "is_thinking_model": false,
+            "max_retries": 2,
+            "retry_base_delay_ms": 0,
+        }))
+        .unwrap()
+    }
+
+    async fn send_to(url: &str) -> (Result<reqwest::Response, AiError>, Vec<u32>) {
+        let payload = request(url);
+        let mut retries = Vec::new();
+        let result = send(
+            &RetryPolicy::from_request(&payload),
+            provider::backend_for(&payload),
+            &payload,
+            &CancellationToken::new(),
+            &mut |attempt, _, _| {
+                retries.push(attempt);
+                Ok(())
+            },
+        )
+        .await;
+        (result, retries)
+    }
+
+    #[tokio::test]
+    async fn retries_before_the_first_byte_only() {
+        let (url, server) = mock_server::serve(vec![
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                .to_string(),
+            // Breaks off well short of its announced length.
+            "HTTP/1.1 200 OK\r\nContent-Length: 1000\r\nConnection: close\r\n\r\ndata: {}".to_string(),
+        ]);
+        let (result, retries) = send_to(&url).await;
+        assert_eq!(retries, [1]);
+        // The broken body comes back as an error of the response, not a new request.
+        assert!(result.unwrap().text().await.is_err());
+        assert_eq!(server.join().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn gives_up_at_once_on_a_permanent_error() {
+        let body = r#"{"error": {"message": "This model's maximum context length is 4096 tokens"}}"#;
+        let (url, server) = mock_server::serve(vec![format!(
+            "HTTP/1.1 400 Bad Request\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )]);
+        let (result, retries) = send_to(&url).await;
+        assert!(matches!(result, Err(AiError::ContextOverflow { .. })));
+        assert!(retries.is_empty());
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+/// Token counts and speed figures reported by the server for one response.
+/// Providers report these in different events (Anthropic splits prompt and
+/// completion counts), so partial reports are merged.
+#[derive(Default, Clone)]
+pub(crate) struct Usage {
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    /// Generation speed measured by the server itself (llama.cpp `timings`, Ollama).
+    pub tokens_per_second: Option<f64>,
+    /// Prompt processing speed measured by the server itself.
+    pub prompt_tokens_per_second: Option<f64>,
+}
+
+impl Usage {
+    /// Takes every field `other` knows, keeping ours where it doesn't.
+    pub fn merge(&mut self, other: Usage) {
+        self.prompt_tokens = other.prompt_tokens.or(self.prompt_tokens);
+        self.completion_tokens = other.completion_tokens.or(self.completion_tokens);
+        self.tokens_per_second = other.tokens_per_second.or(self.tokens_per_second);
+        self.prompt_tokens_per_second = other.prompt_tokens_per_second.or(self.prompt_tokens_per_second);
+    }
+
+    /// Just the prompt side, which all choices of an `n` > 1 request share.
+    pub fn prompt_only(&self) -> Usage {
+        Usage {
+            prompt_tokens: self.prompt_tokens,
+            prompt_tokens_per_second: self.prompt_tokens_per_second,
+            ..Default::default()
+        }
+    }
+}
+
+/// Per-response statistics, returned by call_ai_api and stored per swipe variant.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct GenerationStats {
+    pub model: String,
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    pub total_tokens: Option<u32>,
+    /// True if completion_tokens was counted locally because the server sent no usage.
+    #[serde(default)]
+    pub estimated: bool,
+    /// From sending the request (including any retry waits) to the first token.
+    pub time_to_first_token_ms: Option<u64>,
+    /// From sending the request to the end of the stream.
+    pub duration_ms: u64,
+    pub tokens_per_second: Option<f64>,
+    pub prompt_tokens_per_second: Option<f64>,
+}
+
+/// Collects timing and usage while a stream runs.
+pub(crate) struct StatsRecorder {
+    started: Instant,
+    first_token: Option<Instant>,
+    usage: Usage,
+}
+
+impl StatsRecorder {
+    pub fn start() -> Self {
+        StatsRecorder {
+            started: Instant::now(),
+            first_token: None,
+            usage: Usage::default(),
+        }
+    }
+
+    /// Marks the arrival of generated text; only the first call counts.
+    pub fn token_received(&mut self) {
+        self.first_token.get_or_insert_with(Instant::now);
+    }
+
+    pub fn add_usage(&mut self, usage: Usage) {
+        self.usage.merge(usage);
+    }
+
+    /// Builds the final stats. `generated` is everything the model produced
+    /// (reply and reasoning), used to estimate the count when the server didn't
+    /// report one.
+    pub fn finish(self, model: &str, generated: &str) -> GenerationStats {
+        let finished = Instant::now();
+        let usage = self.usage;
+
+        let (completion_tokens, estimated) = match usage.completion_tokens {
+            Some(count) => (Some(count), false),
+            None if !generated.is_empty() => (
+                Some(crate::tokenizer::count_tokens(generated.to_string(), model.to_string())),
+                true,
+            ),
+            None => (None, false),
+        };
+
+        // Measured from the first token so prompt processing doesn't drag the rate
+        // down. The server's own figure is preferred: it excludes network overhead.
+        let measured_tps = match (self.first_token, completion_tokens) {
+            (Some(first), Some(count)) if count > 0 => {
+                let secs = finished.duration_since(first).as_secs_f64();
+                (secs > 0.0).then(|| count as f64 / secs)
+            }
+            _ => None,
+        };
+
+        GenerationStats {
+            model: model.to_string(),
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens,
+            total_tokens: match (usage.prompt_tokens, completion_tokens) {
+                (Some(p), Some(c)) => Some(p + c),
+                _ => None,
+            },
+            estimated,
+            time_to_first_token_ms: self
+                .first_token
+                .map(|first| first.duration_since(self.started).as_millis() as u64),
+            duration_ms: finished.duration_since(self.started).as_millis() as u64,
+            tokens_per_second: usage.tokens_per_second.or(measured_tps),
+            prompt_tokens_per_second: usage.prompt_tokens_per_second,
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use eventsource_stream::Eventsource;
+use futures::stream::{self, Stream, StreamExt};
+use eventsource_stream::EventStreamError;
+use std::pin::Pin;
+
+use super::error::AiError;
+
+/// One event of a streaming response, independent of how it was framed on the wire.
+pub(crate) struct RawEvent {
+    /// SSE event name; empty for unnamed SSE events and NDJSON lines.
+    pub event: String,
+    pub data: String,
+}
+
+pub(crate) type EventStream = Pin<Box<dyn Stream<Item = Result<RawEvent, AiError>> + Send>>;
+
+/// How a provider frames its streaming response.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StreamFormat {
+    /// Server-Sent Events (`data: {...}\n\n`).
+    Sse,
+    /// Newline-delimited JSON, one object per line (Ollama's native API).
+    Ndjson,
+}
+
+/// Turns an HTTP response into a stream of events according to `format`.
+pub(crate) fn events(res: reqwest::Response, format: StreamFormat) -> EventStream {
+    match format {
+        StreamFormat::Sse => Box::pin(res.bytes_stream().eventsource().map(|result| {
+            result
+                .map(|event| RawEvent { event: event.event, data: event.data })
+                .map_err(|e| match e {
+                    EventStreamError::Transport(e) => AiError::from(e),
+                    other => AiError::MalformedStream { message: other.to_string() },
+                })
+        })),
+        StreamFormat::Ndjson => ndjson_events(res),
+    }
+}
+
+/// Splits the body into lines as the bytes arrive. Lines are cut at the byte level
+/// and only decoded once complete, so a multi-byte character split across two
+/// network chunks is never mangled.
+fn ndjson_events(res: reqwest::Response) -> EventStream {
+    struct State<S> {
+        bytes: S,
+        buffer: Vec<u8>,
+        finished: bool,
+    }
+
+    let state = State {
+        bytes: res.bytes_stream(),
+        buffer: Vec::new(),
+        finished: false,
+    };
+
+    Box::pin(stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(pos) = state.buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = state.buffer.drain(..=pos).collect();
+                let line = String::from_utf8_lossy(&line).trim().to_string();
+                if line.is_empty() {
+                    continue;
+                }
+                return Some((Ok(RawEvent { event: String::new(), data: line }), state));
+            }
+
+            if state.finished {
+                // A final object without a trailing newline.
+                let rest = std::mem::take(&mut state.buffer);
+                let line = String::from_utf8_lossy(&rest).trim().to_string();
+                if line.is_empty() {
+                    return None;
+                }
+                return Some((Ok(RawEvent { event: String::new(), data: line }), state));
+            }
+
+            match state.bytes.next().await {
+                Some(Ok(chunk)) => state.buffer.extend_from_slice(&chunk),
+                Some(Err(e)) => return Some((Err(AiError::from(e)), state)),
+                None => state.finished = true,
+            }
+        }
+    }))
+}
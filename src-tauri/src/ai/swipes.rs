@@ -0,0 +1,185 @@
+use serde::Serialize;
+use tauri::{Manager, Window};
+
+use crate::database::messages::{self, NewVariant};
+use crate::database::world_info::timed_effects;
+
+use super::{
+    provider, stream_generation, AiError, AiRequest, ChoiceOutcome, GenerationStats,
+    StartedGeneration, CHAT_CHANNEL,
+};
+
+// Upper bound for one batch, so a typo can't fire off hundreds of requests.
+const MAX_SWIPES: u32 = 8;
+
+/// One finished choice of a generate_swipes batch.
+#[derive(Serialize)]
+pub struct SwipeResult {
+    pub choice_index: usize,
+    /// The ID this choice's tokens were emitted under.
+    pub generation_id: String,
+    /// Index of the swipe variant it was saved as; None if it produced no text.
+    pub swipe_index: Option<i64>,
+    pub stats: Option<GenerationStats>,
+    /// Set if this choice broke off (text that arrived before that is still
+    /// saved) or if saving the batch failed.
+    pub error: Option<AiError>,
+}
+
+#[derive(Serialize)]
+pub struct SwipeBatchResult {
+    /// ID of the whole batch; stop_generation() with it stops every choice.
+    pub generation_id: String,
+    pub swipes: Vec<SwipeResult>,
+    /// Indices of the choices that broke off or couldn't be saved; see SwipeResult::error.
+    pub failed: Vec<usize>,
+    pub cancelled: bool,
+}
+
+/// Generates `count` new swipe variants for an assistant message at once.
+/// Providers that support `n` get a single request for all choices; the others
+/// (or all of them, with `parallel` set, for servers that silently ignore `n`)
+/// get one request per choice, run side by side.
+/// Choice i streams its tokens under the ID "<generation_id>:<i>", so each
+/// choice can be shown on its own. Every result with text is appended to the
+/// message in choice order, all in one transaction.
+#[tauri::command]
+pub async fn generate_swipes(
+    window: Window,
+    mut payload: AiRequest,
+    message_id: String,
+    count: u32,
+    parallel: Option<bool>,
+) -> Result<SwipeBatchResult, AiError> {
+    // A prompt built from the chat ends right before the message being swiped.
+    payload.prompt_options_mut().before_message_id.get_or_insert_with(|| message_id.clone());
+    // One registration for the whole batch: every request shares its token.
+    let StartedGeneration { id: generation_id, guard: _guard, token, timed } =
+        payload.start(window.app_handle()).await?;
+    let Some(timed) = timed else {
+        return Ok(SwipeBatchResult {
+            generation_id,
+            swipes: Vec::new(),
+            failed: Vec::new(),
+            cancelled: true,
+        });
+    };
+
+    let count = count.clamp(1, MAX_SWIPES);
+    let choice_ids: Vec<String> = (0..count)
+        .map(|i| format!("{}:{}", generation_id, i))
+        .collect();
+
+    let use_n = provider::backend_for(&payload).supports_n() && !parallel.unwrap_or(false);
+
+    let (results, cancelled): (Vec<(ChoiceOutcome, Option<AiError>)>, bool) = if use_n {
+        payload.n = Some(count);
+        let outcome = stream_generation(
+            &window,
+            &payload,
+            &generation_id,
+            CHAT_CHANNEL,
+            &choice_ids,
+            &token,
+            &mut |_, _| {},
+        )
+        .await?;
+        let error = outcome.error;
+        let results = outcome
+            .choices
+            .into_iter()
+            .map(|choice| (choice, error.clone()))
+            .collect();
+        (results, outcome.cancelled)
+    } else {
+        let requests = choice_ids.iter().map(|choice_id| {
+            // Each request is tagged with its choice ID, which is also what
+            // KoboldCpp's abort targets.
+            let mut request = payload.clone();
+            request.generation_id = Some(choice_id.clone());
+            request.n = None;
+            let window = &window;
+            let token = &token;
+            async move {
+                let ids = std::slice::from_ref(choice_id);
+                let outcome =
+                    stream_generation(window, &request, choice_id, CHAT_CHANNEL, ids, token, &mut |_, _| {})
+                        .await;
+                match outcome {
+                    Ok(outcome) => (
+                        outcome.choices.into_iter().next().unwrap_or_default(),
+                        outcome.error,
+                    ),
+                    Err(e) => (ChoiceOutcome::default(), Some(e)),
+                }
+            }
+        });
+        let results = futures::future::join_all(requests).await;
+        (results, token.is_cancelled())
+    };
+
+    // Every choice with text is saved in one transaction, so a failure can't
+    // leave the message with only some of them.
+    let app = window.app_handle().clone();
+    let finished: Vec<(usize, NewVariant)> = results
+        .iter()
+        .zip(&choice_ids)
+        .enumerate()
+        .filter(|(_, ((choice, _), _))| !choice.reply.is_empty())
+        .map(|(choice_index, ((choice, _), choice_id))| {
+            let variant = NewVariant {
+                content: choice.reply.clone(),
+                stats: choice.stats.clone(),
+                reasoning: Some(choice.reasoning.clone()).filter(|r| !r.is_empty()),
+                generation_id: Some(choice_id.clone()),
+            };
+            (choice_index, variant)
+        })
+        .collect();
+    let (saved_choices, variants): (Vec<usize>, Vec<NewVariant>) = finished.into_iter().unzip();
+    let saved = messages::add_swipe_variants(&app, &message_id, &variants);
+
+    let mut swipes = Vec::with_capacity(results.len());
+    for (choice_index, ((choice, error), choice_id)) in results.into_iter().zip(choice_ids).enumerate() {
+        let position = saved_choices.iter().position(|&c| c == choice_index);
+        let (swipe_index, error) = match (position, &saved) {
+            (Some(n), Ok(indices)) => (Some(indices[n]), error),
+            (Some(_), Err(message)) => (None, Some(AiError::Internal { message: message.clone() })),
+            (None, _) => (None, error),
+        };
+        swipes.push(SwipeResult {
+            choice_index,
+            generation_id: choice_id,
+            swipe_index,
+            stats: choice.stats,
+            error,
+        });
+    }
+    let failed = swipes
+        .iter()
+        .filter(|s| s.error.is_some())
+        .map(|s| s.choice_index)
+        .collect();
+
+    // Only a batch that produced nothing at all counts as failed.
+    if swipes.iter().all(|s| s.swipe_index.is_none()) {
+        if let Some(error) = swipes.iter().find_map(|s| s.error.clone()) {
+            return Err(error);
+        }
+    }
+
+    // Recorded once for the batch, and only if a choice finished: all of
+    // them share the prompt, so they'd start the same windows.
+    if !cancelled && swipes.iter().any(|s| s.swipe_index.is_some() && s.error.is_none()) {
+        if let Err(e) = timed_effects::record_started(&app, &timed) {
+            eprintln!("Failed to record world info timed effects: {}", e);
+        }
+    }
+
+    Ok(SwipeBatchResult {
+        generation_id,
+        swipes,
+        failed,
+        cancelled,
+    })
+}
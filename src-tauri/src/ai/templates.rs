@@ -0,0 +1,305 @@
+use minijinja::{context, Environment, Error, ErrorKind};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Instruct formats the chat history can be rendered into for text-completion
+/// backends. `Custom` uses a user-supplied Jinja template in the Hugging Face
+/// `chat_template` style.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum InstructTemplate {
+    #[serde(alias = "chatML")]
+    ChatMl,
+    Llama3,
+    Mistral,
+    Gemma,
+    #[default]
+    Alpaca,
+    Custom,
+}
+
+/// A rendered text-completion prompt plus the stop strings that end the
+/// assistant's turn in that format.
+pub(crate) struct RenderedPrompt {
+    pub prompt: String,
+    pub stop: Vec<String>,
+}
+
+/// Static description of a built-in instruct format. Every turn is rendered as
+/// `prefix + content + suffix`; the prompt ends on an open assistant prefix.
+struct Format {
+    bos: &'static str,
+    system: (&'static str, &'static str),
+    user: (&'static str, &'static str),
+    assistant: (&'static str, &'static str),
+    /// Formats without a system role fold the system text into the first user turn.
+    system_in_first_user: bool,
+    stop: &'static [&'static str],
+}
+
+const CHATML: Format = Format {
+    bos: "",
+    system: ("<|im_start|>system\n", "<|im_end|>\n"),
+    user: ("<|im_start|>user\n", "<|im_end|>\n"),
+    assistant: ("<|im_start|>assistant\n", "<|im_end|>\n"),
+    system_in_first_user: false,
+    stop: &["<|im_end|>", "<|im_start|>"],
+};
+
+const LLAMA3: Format = Format {
+    bos: "<|begin_of_text|>",
+    system: ("<|start_header_id|>system<|end_header_id|>\n\n", "<|eot_id|>"),
+    user: ("<|start_header_id|>user<|end_header_id|>\n\n", "<|eot_id|>"),
+    assistant: ("<|start_header_id|>assistant<|end_header_id|>\n\n", "<|eot_id|>"),
+    system_in_first_user: false,
+    stop: &["<|eot_id|>", "<|start_header_id|>"],
+};
+
+const MISTRAL: Format = Format {
+    bos: "<s>",
+    system: ("", ""),
+    user: ("[INST] ", " [/INST]"),
+    assistant: ("", "</s>"),
+    system_in_first_user: true,
+    stop: &["</s>", "[INST]"],
+};
+
+const GEMMA: Format = Format {
+    bos: "<bos>",
+    system: ("", ""),
+    user: ("<start_of_turn>user\n", "<end_of_turn>\n"),
+    assistant: ("<start_of_turn>model\n", "<end_of_turn>\n"),
+    system_in_first_user: true,
+    stop: &["<end_of_turn>", "<start_of_turn>"],
+};
+
+const ALPACA: Format = Format {
+    bos: "",
+    system: ("", "\n\n"),
+    user: ("### Instruction:\n", "\n\n"),
+    assistant: ("### Response:\n", "\n\n"),
+    system_in_first_user: false,
+    stop: &["### Instruction:", "### Response:"],
+};
+
+fn role_and_content(msg: &Value) -> (&str, &str) {
+    let role = match msg["role"].as_str().unwrap_or("user") {
+        "system" => "system",
+        "assistant" => "assistant",
+        _ => "user",
+    };
+    (role, msg["content"].as_str().unwrap_or_default())
+}
+
+/// Splits off a trailing assistant message that is to be continued rather than
+/// answered.
+fn split_continued(messages: &[Value], continue_last: bool) -> (&[Value], Option<&str>) {
+    match messages.split_last() {
+        Some((last, rest)) if continue_last && role_and_content(last).0 == "assistant" => {
+            (rest, Some(role_and_content(last).1))
+        }
+        _ => (messages, None),
+    }
+}
+
+fn render_builtin(format: &Format, messages: &[Value], continue_last: bool) -> String {
+    let (messages, continued) = split_continued(messages, continue_last);
+    let mut prompt = String::from(format.bos);
+    let mut pending_system: Vec<&str> = Vec::new();
+
+    for msg in messages {
+        let (role, content) = role_and_content(msg);
+        match role {
+            "system" if format.system_in_first_user => pending_system.push(content),
+            "system" => {
+                prompt.push_str(format.system.0);
+                prompt.push_str(content);
+                prompt.push_str(format.system.1);
+            }
+            "assistant" => {
+                prompt.push_str(format.assistant.0);
+                prompt.push_str(content);
+                prompt.push_str(format.assistant.1);
+            }
+            _ => {
+                prompt.push_str(format.user.0);
+                if !pending_system.is_empty() {
+                    prompt.push_str(&pending_system.join("\n\n"));
+                    prompt.push_str("\n\n");
+                    pending_system.clear();
+                }
+                prompt.push_str(content);
+                prompt.push_str(format.user.1);
+            }
+        }
+    }
+
+    // System text with no user turn after it still has to reach the model.
+    if !pending_system.is_empty() {
+        prompt.push_str(format.user.0);
+        prompt.push_str(&pending_system.join("\n\n"));
+        prompt.push_str(format.user.1);
+    }
+
+    prompt.push_str(format.assistant.0);
+    // Left open (no suffix), so the model picks up mid-reply.
+    if let Some(continued) = continued {
+        prompt.push_str(continued);
+    }
+    prompt
+}
+
+/// Renders a Hugging Face style chat template. `add_generation_prompt` is always
+/// set, since the prompt has to end on an open assistant turn.
+fn render_jinja(source: &str, messages: &[Value]) -> Result<String, String> {
+    let mut env = Environment::new();
+    // HF templates lean on Python string methods (.strip(), .startswith(), ...).
+    env.set_unknown_method_callback(minijinja_contrib::pycompat::unknown_method_callback);
+    env.add_function("raise_exception", |msg: String| -> Result<String, Error> {
+        Err(Error::new(ErrorKind::InvalidOperation, msg))
+    });
+    env.add_template("chat", source)
+        .map_err(|e| format!("Invalid chat template: {}", e))?;
+
+    env.get_template("chat")
+        .and_then(|t| {
+            t.render(context! {
+                messages => messages,
+                add_generation_prompt => true,
+                bos_token => "",
+                eos_token => "",
+            })
+        })
+        .map_err(|e| format!("Failed to render chat template: {}", e))
+}
+
+/// Finds the stop strings of an arbitrary template by rendering a probe
+/// conversation and reading off what the template puts between the end of an
+/// assistant reply and the start of the next user message.
+fn derive_stop_strings(source: &str) -> Vec<String> {
+    const REPLY: &str = "\u{1}REPLY\u{1}";
+    const NEXT: &str = "\u{1}NEXT\u{1}";
+    let probe = vec![
+        serde_json::json!({ "role": "user", "content": "\u{1}FIRST\u{1}" }),
+        serde_json::json!({ "role": "assistant", "content": REPLY }),
+        serde_json::json!({ "role": "user", "content": NEXT }),
+    ];
+
+    let Ok(rendered) = render_jinja(source, &probe) else {
+        return Vec::new();
+    };
+    let (Some(reply_at), Some(next_at)) = (rendered.find(REPLY), rendered.find(NEXT)) else {
+        return Vec::new();
+    };
+    let reply_end = reply_at + REPLY.len();
+    if next_at <= reply_end {
+        return Vec::new();
+    }
+
+    // E.g. "<|im_end|>\n<|im_start|>user\n": the end-of-turn token closes the
+    // reply, the rest opens the next user turn. Both are useful stops on their
+    // own. The token is split off by itself since some formats put the next
+    // header right after it, like Llama 3's "<|eot_id|><|start_header_id|>...".
+    let between = rendered[reply_end..next_at].trim_start();
+    let (end_of_turn, rest) = match leading_token(between) {
+        Some(token) => (Some(token), &between[token.len()..]),
+        None => (None, between),
+    };
+    let mut stop: Vec<String> = end_of_turn
+        .into_iter()
+        .chain(rest.lines().map(str::trim).filter(|line| !line.is_empty()))
+        .map(str::to_string)
+        .collect();
+    stop.dedup();
+    stop
+}
+
+/// The special token `text` starts with, like "<|eot_id|>" or "</s>".
+fn leading_token(text: &str) -> Option<&str> {
+    let inner = text.strip_prefix('<')?;
+    let end = inner.find('>')?;
+    let name = &inner[..end];
+    let is_token = !name.is_empty() && !name.contains(|c: char| c == '<' || c.is_whitespace());
+    is_token.then(|| &text[..end + 2])
+}
+
+/// Renders the chat into a single prompt in the given instruct format.
+/// With `continue_last`, a trailing assistant message is left open instead of
+/// closed, so the model continues it rather than starting a new reply.
+pub(crate) fn render_prompt(
+    template: InstructTemplate,
+    custom_template: Option<&str>,
+    messages: &[Value],
+    continue_last: bool,
+) -> Result<RenderedPrompt, String> {
+    let format = match template {
+        InstructTemplate::ChatMl => &CHATML,
+        InstructTemplate::Llama3 => &LLAMA3,
+        InstructTemplate::Mistral => &MISTRAL,
+        InstructTemplate::Gemma => &GEMMA,
+        InstructTemplate::Alpaca => &ALPACA,
+        InstructTemplate::Custom => {
+            let source = custom_template
+                .filter(|s| !s.trim().is_empty())
+                .ok_or_else(|| "Custom instruct template selected but none was provided".to_string())?;
+            // Rendered up to an open assistant turn, then the continued text is
+            // appended raw; templates have no notion of an unfinished message.
+            let (messages, continued) = split_continued(messages, continue_last);
+            let mut prompt = render_jinja(source, messages)?;
+            prompt.push_str(continued.unwrap_or_default());
+            return Ok(RenderedPrompt {
+                prompt,
+                stop: derive_stop_strings(source),
+            });
+        }
+    };
+
+    Ok(RenderedPrompt {
+        prompt: render_builtin(format, messages, continue_last),
+        stop: format.stop.iter().map(|s| s.to_string()).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The chat template Llama 3 Instruct ships with.
+    const LLAMA3_JINJA: &str = "{% set loop_messages = messages %}{% for message in loop_messages %}\
+        {% set content = '<|start_header_id|>' + message['role'] + '<|end_header_id|>\n\n' + message['content'] | trim + '<|eot_id|>' %}\
+        {% if loop.index0 == 0 %}{% set content = bos_token + content %}{% endif %}{{ content }}{% endfor %}\
+        {% if add_generation_prompt %}{{ '<|start_header_id|>assistant<|end_header_id|>\n\n' }}{% endif %}";
+
+    #[test]
+    fn llama3_template_stops_at_the_end_of_turn_token() {
+        assert_eq!(
+            derive_stop_strings(LLAMA3_JINJA),
+            ["<|eot_id|>", "<|start_header_id|>user<|end_header_id|>"]
+        );
+    }
+
+    #[test]
+    fn chatml_template_stops_at_both_markers() {
+        let source = "{% for message in messages %}<|im_start|>{{ message.role }}\n{{ message.content }}<|im_end|>\n{% endfor %}\
+            {% if add_generation_prompt %}<|im_start|>assistant\n{% endif %}";
+        assert_eq!(derive_stop_strings(source), ["<|im_end|>", "<|im_start|>user"]);
+    }
+
+    #[test]
+    fn plain_text_template_stops_at_the_next_header() {
+        let source = "{% for message in messages %}{% if message.role == 'user' %}### Instruction:\n{% else %}### Response:\n{% endif %}\
+            {{ message.content }}\n\n{% endfor %}{% if add_generation_prompt %}### Response:\n{% endif %}";
+        assert_eq!(derive_stop_strings(source), ["### Instruction:"]);
+    }
+
+    #[test]
+    fn custom_template_prompt_carries_the_derived_stops() {
+        let messages = vec![serde_json::json!({ "role": "user", "content": "Hi" })];
+        let rendered = render_prompt(InstructTemplate::Custom, Some(LLAMA3_JINJA), &messages, false).unwrap();
+        assert_eq!(
+            rendered.prompt,
+            "<|start_header_id|>user<|end_header_id|>\n\nHi<|eot_id|><|start_header_id|>assistant<|end_header_id|>\n\n"
+        );
+        assert_eq!(rendered.stop[0], "<|eot_id|>");
+    }
+}
@@ -0,0 +1,60 @@
+//! Hooks for the benchmarks in benches/. Only built with the "bench" feature.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::database::world_info::activation::{self, Lorebook, Timeline, DEFAULT_SCAN_DEPTH};
+use crate::database::world_info::matcher::KeyMatcher;
+pub use crate::database::world_info::WorldInfoEntry;
+
+/// A lorebook the way a saved one is matched: its entries and their
+/// prebuilt matcher.
+pub struct Book {
+    entries: Vec<WorldInfoEntry>,
+    matcher: Arc<KeyMatcher>,
+}
+
+impl Book {
+    /// Compiles the keys, the work saving a book triggers.
+    pub fn new(entries: Vec<WorldInfoEntry>) -> Self {
+        let matcher = Arc::new(KeyMatcher::new(&entries));
+        Book { entries, matcher }
+    }
+
+    /// Runs activation over `messages` with the default scan depth and no
+    /// budget or timed effects. Returns how many entries activated.
+    pub fn activate(&self, messages: &[&str], max_recursion: u32) -> usize {
+        self.activate_with(messages, max_recursion, &Conditions::default())
+    }
+
+    /// Like activate(), under a token budget and timed effects.
+    pub fn activate_with(&self, messages: &[&str], max_recursion: u32, conditions: &Conditions) -> usize {
+        let book = Lorebook {
+            id: "bench",
+            entries: &self.entries,
+            matcher: self.matcher.clone(),
+            scan_depth: DEFAULT_SCAN_DEPTH,
+            token_budget: conditions.token_budget,
+            semantic_threshold: None,
+            similarity: Vec::new(),
+        };
+        let ids = |ids: &[String]| HashMap::from([(book.id.to_string(), ids.iter().cloned().collect())]);
+        let timeline = Timeline {
+            position: messages.len() as u32,
+            sticky: ids(conditions.sticky),
+            cooldown: ids(conditions.cooldown),
+        };
+        activation::activate(&[book], messages, "", max_recursion, &timeline, 0)
+            .entries
+            .len()
+    }
+}
+
+/// The book's token budget and the entries that are sticky or on cooldown,
+/// by ID, as of the message being generated.
+#[derive(Default)]
+pub struct Conditions<'a> {
+    pub token_budget: Option<u32>,
+    pub sticky: &'a [String],
+    pub cooldown: &'a [String],
+}
@@ -0,0 +1,174 @@
+use tauri::AppHandle;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::database::get_connection;
+
+/// Something a character remembers across chats. Notes without a role apply
+/// whichever persona the user plays; the others only with that persona.
+#[derive(Serialize)]
+pub struct CharacterMemory {
+    pub id: String,
+    pub character_id: String,
+    pub role_id: Option<String>,
+    pub content: String,
+    /// The chat it was extracted from; None for notes the user wrote.
+    pub source_chat_id: Option<String>,
+    /// Disabled notes are kept but left out of prompts.
+    pub enabled: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Payload sent from the frontend when writing a note by hand.
+#[derive(Deserialize)]
+pub struct CharacterMemoryPayload {
+    pub character_id: String,
+    pub role_id: Option<String>,
+    pub content: String,
+}
+
+/// Payload sent from the frontend when editing a note.
+#[derive(Deserialize)]
+pub struct CharacterMemoryUpdate {
+    pub content: String,
+    pub role_id: Option<String>,
+    pub enabled: bool,
+}
+
+const COLUMNS: &str =
+    "id, character_id, role_id, content, source_chat_id, enabled, created_at, updated_at";
+
+fn from_row(row: &rusqlite::Row) -> rusqlite::Result<CharacterMemory> {
+    Ok(CharacterMemory {
+        id: row.get(0)?,
+        character_id: row.get(1)?,
+        role_id: row.get(2)?,
+        content: row.get(3)?,
+        source_chat_id: row.get(4)?,
+        enabled: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+fn get_memory(conn: &Connection, id: &str) -> Result<CharacterMemory, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM character_memories WHERE id = ?1", COLUMNS),
+        params![id],
+        from_row,
+    ).map_err(|e| e.to_string())
+}
+
+/// Saves extracted notes for a character, scoped to `role_id` if set.
+pub(crate) fn insert_memories(
+    app: &AppHandle,
+    character_id: &str,
+    role_id: Option<&str>,
+    source_chat_id: Option<&str>,
+    contents: &[String],
+) -> Result<Vec<CharacterMemory>, String> {
+    let mut conn = get_connection(app)?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let mut ids = Vec::with_capacity(contents.len());
+    for content in contents {
+        let id = Uuid::new_v4().to_string();
+        tx.execute(
+            "INSERT INTO character_memories (id, character_id, role_id, content, source_chat_id)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![id, character_id, role_id, content, source_chat_id],
+        ).map_err(|e| e.to_string())?;
+        ids.push(id);
+    }
+    let saved = ids
+        .iter()
+        .map(|id| get_memory(&tx, id))
+        .collect::<Result<Vec<_>, _>>()?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(saved)
+}
+
+/// The enabled notes of a character that apply with `role_id`: the ones for
+/// every persona and the ones for that persona, oldest first.
+pub(crate) fn active_memories(
+    app: &AppHandle,
+    character_id: &str,
+    role_id: Option<&str>,
+) -> Result<Vec<String>, String> {
+    let conn = get_connection(app)?;
+    let mut stmt = conn.prepare(
+        "SELECT content FROM character_memories
+         WHERE character_id = ?1 AND enabled = 1 AND (role_id IS NULL OR role_id = ?2)
+         ORDER BY created_at, rowid"
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![character_id, role_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+}
+
+/// Removes a deleted character's notes.
+pub(crate) fn delete_memories_of_character(conn: &Connection, character_id: &str) -> Result<(), String> {
+    conn.execute("DELETE FROM character_memories WHERE character_id = ?1", params![character_id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Removes the notes about a deleted persona.
+pub(crate) fn delete_memories_of_role(conn: &Connection, role_id: &str) -> Result<(), String> {
+    conn.execute("DELETE FROM character_memories WHERE role_id = ?1", params![role_id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// A note's text without surrounding whitespace; empty notes are refused.
+fn note_content(content: &str) -> Result<String, String> {
+    let content = content.trim();
+    if content.is_empty() {
+        return Err("A memory needs some content.".to_string());
+    }
+    Ok(content.to_string())
+}
+
+/// Lists every note of a character, for all personas, oldest first.
+#[tauri::command]
+pub fn get_character_memories(app: AppHandle, character_id: String) -> Result<Vec<CharacterMemory>, String> {
+    let conn = get_connection(&app)?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM character_memories WHERE character_id = ?1 ORDER BY created_at, rowid",
+        COLUMNS
+    )).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![character_id], from_row)
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn create_character_memory(app: AppHandle, payload: CharacterMemoryPayload) -> Result<CharacterMemory, String> {
+    let content = note_content(&payload.content)?;
+    insert_memories(&app, &payload.character_id, payload.role_id.as_deref(), None, &[content])?
+        .pop()
+        .ok_or_else(|| "Memory was not saved".to_string())
+}
+
+#[tauri::command]
+pub fn update_character_memory(app: AppHandle, id: String, payload: CharacterMemoryUpdate) -> Result<(), String> {
+    let content = note_content(&payload.content)?;
+    let conn = get_connection(&app)?;
+    conn.execute(
+        "UPDATE character_memories
+         SET content = ?1, role_id = ?2, enabled = ?3, updated_at = (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+         WHERE id = ?4",
+        params![content, payload.role_id, payload.enabled, id],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn delete_character_memory(app: AppHandle, id: String) -> Result<(), String> {
+    let conn = get_connection(&app)?;
+    conn.execute("DELETE FROM character_memories WHERE id = ?1", params![id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
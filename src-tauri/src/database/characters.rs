@@ -2,7 +2,7 @@ use tauri::AppHandle;
 use rusqlite::params;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use crate::database::get_connection;
+use crate::database::{character_memories, get_connection};
 use base64::{engine::general_purpose, Engine as _};
 use image::{ImageFormat};
 use webp::{Encoder, WebPMemory};
@@ -139,6 +139,47 @@ pub fn get_custom_characters(app: AppHandle) -> Result<Vec<DbCharacter>, String>
     Ok(list)
 }
 
+/// Loads a single saved character, for backend code that needs the card.
+/// Returns None for IDs that aren't in the database (e.g. the built-in characters).
+pub(crate) fn get_character(app: &AppHandle, id: &str) -> Result<Option<DbCharacter>, String> {
+    let conn = get_connection(app)?;
+
+    let result = conn.query_row(
+        "SELECT id, name, desc, personality, scenario, greeting,
+                alternate_greetings, mes_example, creator_notes, tags,
+                v3_spec, initials, color, world_info_ids, avatar
+         FROM characters WHERE id = ?1",
+        params![id],
+        |row| {
+            Ok(DbCharacter {
+                id:                 row.get(0)?,
+                name:               row.get(1)?,
+                desc:               row.get(2)?,
+                personality:        row.get(3)?,
+                scenario:           row.get(4)?,
+                greeting:           row.get(5)?,
+                alternate_greetings: row.get(6)?,
+                mes_example:        row.get(7)?,
+                creator_notes:      row.get(8)?,
+                tags:               row.get(9)?,
+                v3_spec:            row.get(10)?,
+                initials:           row.get(11)?,
+                color:              row.get(12)?,
+                world_info_ids: serde_json::from_str(
+                    &row.get::<_, Option<String>>(13)?.unwrap_or_default()
+                ).unwrap_or_default(),
+                avatar: row.get(14)?,
+            })
+        },
+    );
+
+    match result {
+        Ok(character) => Ok(Some(character)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
 /// Inserts a new character. Avatar processing runs on a background thread.
 #[tauri::command]
 pub fn create_character(app: AppHandle, payload: CreateCharacterPayload) -> Result<String, String> {
@@ -273,6 +314,7 @@ pub fn delete_character(app: AppHandle, id: String) -> Result<(), String> {
 
     conn.execute("DELETE FROM characters WHERE id = ?1", params![id])
         .map_err(|e| e.to_string())?;
+    character_memories::delete_memories_of_character(&conn, &id)?;
 
     // Clean up from hidden list
     let raw: Option<String> = conn
@@ -2,7 +2,7 @@ use tauri::AppHandle;
 use rusqlite::{params};
 use uuid::Uuid;
 use serde::Serialize;
-use crate::database::get_connection;
+use crate::database::{get_connection, world_info};
 
 /// Represents a chat session with an AI character in the database.
 #[derive(Serialize)]
@@ -137,13 +137,13 @@ pub fn clone_chat_from_message(
     // rowid is a tiebreaker for messages sharing the same created_at second
     // (e.g. rapid inserts) — it always reflects true insertion order.
     let mut stmt = tx.prepare(
-        "SELECT id, role, content, swipe_variants, swipe_index
+        "SELECT id, role, content, swipe_variants, swipe_index, swipe_stats, swipe_reasoning
          FROM messages WHERE conversation_id = ?1 ORDER BY created_at ASC, rowid ASC"
     ).map_err(|e| e.to_string())?;
 
-    let all_messages: Vec<(String, String, String, String, i64)> = stmt
+    let all_messages: Vec<(String, String, String, String, i64, String, String)> = stmt
         .query_map(params![chat_id], |row| {
-            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?, row.get(6)?))
         }).map_err(|e| e.to_string())?
         .collect::<Result<_, _>>()
         .map_err(|e| e.to_string())?;
@@ -166,17 +166,22 @@ pub fn clone_chat_from_message(
     ).map_err(|e| e.to_string())?;
 
     // Copy every message up to the cut-off with fresh ids, preserving role,
-    // content and swipe history. Inserted in order so created_at / rowid
-    // ordering matches the original conversation.
-    for (_, role, content, swipe_variants, swipe_index) in messages_to_copy {
+    // content and swipe history (including per-variant stats and reasoning).
+    // Inserted in order so created_at / rowid ordering matches the original
+    // conversation.
+    for (_, role, content, swipe_variants, swipe_index, swipe_stats, swipe_reasoning) in messages_to_copy {
         let new_msg_id = Uuid::new_v4().to_string();
         tx.execute(
-            "INSERT INTO messages (id, conversation_id, role, content, swipe_variants, swipe_index)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            params![new_msg_id, new_chat_id, role, content, swipe_variants, swipe_index],
+            "INSERT INTO messages (id, conversation_id, role, content, swipe_variants, swipe_index, swipe_stats, swipe_reasoning)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![new_msg_id, new_chat_id, role, content, swipe_variants, swipe_index, swipe_stats, swipe_reasoning],
         ).map_err(|e| e.to_string())?;
     }
 
+    // World info sticky / cooldown state as of the cut-off.
+    world_info::timed_effects::copy_timed_effects(&tx, &chat_id, &new_chat_id, cut_idx as u32)?;
+    world_info::links::copy_chat_world_infos(&tx, &chat_id, &new_chat_id)?;
+
     tx.commit().map_err(|e| e.to_string())?;
 
     Ok(new_chat_id)
@@ -264,4 +269,14 @@ pub fn get_summary_meta(app: AppHandle, chat_id: String) -> Result<SummaryMeta,
         }),
     ).map_err(|e| e.to_string())?;
     Ok(result)
+}
+
+/// Returns the ID of the character a conversation is held with.
+pub(crate) fn get_chat_character_id(app: &AppHandle, chat_id: &str) -> Result<Option<String>, String> {
+    let conn = get_connection(app)?;
+    conn.query_row(
+        "SELECT character_id FROM conversations WHERE id = ?1",
+        params![chat_id],
+        |row| row.get(0),
+    ).map_err(|e| e.to_string())
 }
\ No newline at end of file
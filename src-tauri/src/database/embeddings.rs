@@ -0,0 +1,65 @@
+use tauri::AppHandle;
+use rusqlite::params;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use crate::database::get_connection;
+
+/// Cache key of a text: hex SHA-256 of its bytes. Entries with the same
+/// content share a vector, across books.
+pub(crate) fn content_hash(text: &str) -> String {
+    Sha256::digest(text.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub(crate) fn to_blob(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+pub(crate) fn from_blob(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+/// The cached vectors `model` produced for any of `hashes`, by hash.
+pub(crate) fn get_embeddings(
+    app: &AppHandle,
+    model: &str,
+    hashes: &[String],
+) -> Result<HashMap<String, Vec<f32>>, String> {
+    let conn = get_connection(app)?;
+    let mut stmt = conn.prepare(
+        "SELECT vector FROM embeddings WHERE content_hash = ?1 AND model = ?2"
+    ).map_err(|e| e.to_string())?;
+
+    let mut found = HashMap::new();
+    for hash in hashes {
+        match stmt.query_row(params![hash, model], |row| row.get::<_, Vec<u8>>(0)) {
+            Ok(blob) => {
+                found.insert(hash.clone(), from_blob(&blob));
+            }
+            Err(rusqlite::Error::QueryReturnedNoRows) => {}
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    Ok(found)
+}
+
+/// Caches vectors `model` produced, by content hash.
+pub(crate) fn store_embeddings(
+    app: &AppHandle,
+    model: &str,
+    vectors: &[(String, Vec<f32>)],
+) -> Result<(), String> {
+    let mut conn = get_connection(app)?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    for (hash, vector) in vectors {
+        tx.execute(
+            "INSERT OR REPLACE INTO embeddings (content_hash, model, vector) VALUES (?1, ?2, ?3)",
+            params![hash, model, to_blob(vector)],
+        ).map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
@@ -0,0 +1,118 @@
+use tauri::AppHandle;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use crate::database::{get_connection, settings};
+
+/// Settings key for how many days recorded requests are kept; "0" turns
+/// recording off.
+const RETENTION_SETTING: &str = "prompt_inspector_retention_days";
+const DEFAULT_RETENTION_DAYS: u32 = 30;
+
+/// A request as it was sent for one generation, for the prompt inspector.
+#[derive(Serialize)]
+pub struct GenerationRequest {
+    pub generation_id: String,
+    /// Provider, endpoint, model and the full body, without credentials.
+    pub request: serde_json::Value,
+    pub created_at: String,
+}
+
+fn retention_days(app: &AppHandle) -> Result<u32, String> {
+    Ok(settings::get_setting(app, RETENTION_SETTING)?
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_RETENTION_DAYS))
+}
+
+/// Deletes records older than the retention period, including ones that were
+/// never linked to a message (summaries, impersonation, discarded replies).
+/// Run at startup by init_db.
+pub(crate) fn prune_expired(app: &AppHandle) -> Result<(), String> {
+    let days = retention_days(app)?;
+    get_connection(app)?.execute(
+        "DELETE FROM generation_requests
+         WHERE created_at < strftime('%Y-%m-%dT%H:%M:%fZ', 'now', ?1)",
+        params![format!("-{} days", days)],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Stores the request sent for a generation, under the ID of each choice it
+/// produces. Records aren't tied to a message yet; see link_request.
+pub(crate) fn record_request(
+    app: &AppHandle,
+    generation_ids: &[String],
+    request: &serde_json::Value,
+) -> Result<(), String> {
+    if retention_days(app)? == 0 {
+        return Ok(());
+    }
+
+    let conn = get_connection(app)?;
+    let json = serde_json::to_string(request).map_err(|e| e.to_string())?;
+    for id in generation_ids {
+        conn.execute(
+            "INSERT OR REPLACE INTO generation_requests (generation_id, request) VALUES (?1, ?2)",
+            params![id, json],
+        ).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Ties a recorded request to the swipe variant its reply was saved as.
+/// Without `variant` the message's active variant is used. Does nothing if
+/// no request was recorded under that ID.
+pub(crate) fn link_request(
+    app: &AppHandle,
+    generation_id: &str,
+    message_id: &str,
+    variant: Option<i64>,
+) -> Result<(), String> {
+    link_request_in(&get_connection(app)?, generation_id, message_id, variant)
+}
+
+/// link_request on an open connection, e.g. inside the transaction that
+/// saves the variant.
+pub(crate) fn link_request_in(
+    conn: &Connection,
+    generation_id: &str,
+    message_id: &str,
+    variant: Option<i64>,
+) -> Result<(), String> {
+    conn.execute(
+        "UPDATE generation_requests
+         SET message_id = ?2,
+             variant = COALESCE(?3, (SELECT swipe_index FROM messages WHERE id = ?2))
+         WHERE generation_id = ?1",
+        params![generation_id, message_id, variant],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Returns the request that produced a swipe variant. If it was continued,
+/// that's the latest continuation request. None if nothing was recorded or
+/// it has been pruned.
+#[tauri::command]
+pub fn get_generation_request(
+    app: AppHandle,
+    message_id: String,
+    variant: i64,
+) -> Result<Option<GenerationRequest>, String> {
+    let conn = get_connection(&app)?;
+    let result = conn.query_row(
+        "SELECT generation_id, request, created_at FROM generation_requests
+         WHERE message_id = ?1 AND variant = ?2
+         ORDER BY created_at DESC, rowid DESC LIMIT 1",
+        params![message_id, variant],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)),
+    );
+
+    match result {
+        Ok((generation_id, request, created_at)) => Ok(Some(GenerationRequest {
+            generation_id,
+            request: serde_json::from_str(&request).map_err(|e| e.to_string())?,
+            created_at,
+        })),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
@@ -0,0 +1,119 @@
+use tauri::AppHandle;
+use rusqlite::params;
+use serde::Serialize;
+use std::collections::HashMap;
+use crate::database::embeddings::{from_blob, to_blob};
+use crate::database::get_connection;
+
+/// A run of consecutive messages of a chat that the rolling summary already
+/// covers, kept verbatim for long-term memory recall (see prompt::memory).
+/// `position` numbers the chunks from the start of the chat.
+#[derive(Serialize)]
+pub struct MemoryChunk {
+    pub position: u32,
+    pub first_message_id: String,
+    pub last_message_id: String,
+    pub content: String,
+}
+
+/// Content hash of each stored chunk of a chat embedded with `model`, by position.
+pub(crate) fn get_chunk_hashes(app: &AppHandle, chat_id: &str, model: &str) -> Result<HashMap<u32, String>, String> {
+    let conn = get_connection(app)?;
+    let mut stmt = conn.prepare(
+        "SELECT position, content_hash FROM memory_chunks WHERE chat_id = ?1 AND model = ?2"
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![chat_id, model], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+}
+
+/// Saves chunks with their vectors, replacing what was stored at the same positions.
+pub(crate) fn store_chunks(
+    app: &AppHandle,
+    chat_id: &str,
+    model: &str,
+    chunks: &[(MemoryChunk, String, &[f32])],
+) -> Result<(), String> {
+    let mut conn = get_connection(app)?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    for (chunk, hash, vector) in chunks {
+        tx.execute(
+            "INSERT OR REPLACE INTO memory_chunks
+                (chat_id, position, first_message_id, last_message_id, content, content_hash, model, vector)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                chat_id,
+                chunk.position,
+                chunk.first_message_id,
+                chunk.last_message_id,
+                chunk.content,
+                hash,
+                model,
+                to_blob(vector),
+            ],
+        ).map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// The chunks of a chat before position `below`, with their `model` vectors.
+pub(crate) fn get_chunk_vectors(
+    app: &AppHandle,
+    chat_id: &str,
+    model: &str,
+    below: u32,
+) -> Result<Vec<(MemoryChunk, Vec<f32>)>, String> {
+    let conn = get_connection(app)?;
+    let mut stmt = conn.prepare(
+        "SELECT position, first_message_id, last_message_id, content, vector
+         FROM memory_chunks
+         WHERE chat_id = ?1 AND model = ?2 AND position < ?3
+         ORDER BY position"
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![chat_id, model, below], |row| {
+            Ok((
+                MemoryChunk {
+                    position: row.get(0)?,
+                    first_message_id: row.get(1)?,
+                    last_message_id: row.get(2)?,
+                    content: row.get(3)?,
+                },
+                from_blob(&row.get::<_, Vec<u8>>(4)?),
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+}
+
+/// Lists the memory chunks stored for a conversation, oldest first.
+#[tauri::command]
+pub fn get_memory_chunks(app: AppHandle, chat_id: String) -> Result<Vec<MemoryChunk>, String> {
+    let conn = get_connection(&app)?;
+    let mut stmt = conn.prepare(
+        "SELECT position, first_message_id, last_message_id, content
+         FROM memory_chunks WHERE chat_id = ?1 ORDER BY position"
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![chat_id], |row| {
+            Ok(MemoryChunk {
+                position: row.get(0)?,
+                first_message_id: row.get(1)?,
+                last_message_id: row.get(2)?,
+                content: row.get(3)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+}
+
+/// Forgets a conversation's memory chunks. They're rebuilt from the
+/// summarized messages on the next generation, if memory is on.
+#[tauri::command]
+pub fn clear_memory(app: AppHandle, chat_id: String) -> Result<(), String> {
+    let conn = get_connection(&app)?;
+    conn.execute("DELETE FROM memory_chunks WHERE chat_id = ?1", params![chat_id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
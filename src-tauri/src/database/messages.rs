@@ -1,8 +1,9 @@
 use tauri::AppHandle;
-use rusqlite::{params};
-use serde::Serialize;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use crate::database::get_connection;
+use crate::ai::GenerationStats;
+use crate::database::{generation_requests, get_connection};
 
 /// Database representation of a single chat message within a conversation.
 #[derive(Serialize)]
@@ -15,6 +16,57 @@ pub struct DbMessage {
     pub swipe_variants: String,
     /// Zero-based index pointing to the currently displayed variant.
     pub swipe_index: i64,
+    /// JSON array parallel to `swipe_variants`: the GenerationStats of each
+    /// variant, or null where there are none.
+    pub swipe_stats: String,
+    /// JSON array parallel to `swipe_variants`: the reasoning ("thinking") text
+    /// behind each variant, or null where there is none.
+    pub swipe_reasoning: String,
+    /// "streaming" while a backend-owned generation is writing into the active
+    /// variant, "interrupted" if it broke off, "complete" otherwise.
+    pub generation_status: String,
+}
+
+/// State of a backend-owned generation writing into a message.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum GenerationStatus {
+    Streaming,
+    Complete,
+    Interrupted,
+}
+
+impl GenerationStatus {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            GenerationStatus::Streaming => "streaming",
+            GenerationStatus::Complete => "complete",
+            GenerationStatus::Interrupted => "interrupted",
+        }
+    }
+}
+
+/// Puts `value` at `index` of a JSON array column that runs parallel to
+/// swipe_variants, padding with nulls if older variants have no entry yet.
+fn set_variant_slot(json: &str, index: usize, value: serde_json::Value) -> Result<String, String> {
+    let mut slots: Vec<serde_json::Value> = serde_json::from_str(json).unwrap_or_default();
+    if slots.len() <= index {
+        slots.resize(index + 1, serde_json::Value::Null);
+    }
+    slots[index] = value;
+    serde_json::to_string(&slots).map_err(|e| e.to_string())
+}
+
+/// Puts `content` at `index` of swipe_variants. Padded with empty strings,
+/// not nulls: the column is read back as a list of strings, and a single
+/// null would make the whole list unreadable.
+fn set_variant_text(json: &str, index: usize, content: &str) -> Result<String, String> {
+    let mut variants: Vec<String> = serde_json::from_str(json).unwrap_or_default();
+    if variants.len() <= index {
+        variants.resize(index + 1, String::new());
+    }
+    variants[index] = content.to_string();
+    serde_json::to_string(&variants).map_err(|e| e.to_string())
 }
 
 /// Retrieves the full, chronological message history for a specific conversation.
@@ -25,7 +77,7 @@ pub struct DbMessage {
 pub fn get_messages(app: AppHandle, chat_id: String) -> Result<Vec<DbMessage>, String> {
     let conn = get_connection(&app)?;
     let mut stmt = conn.prepare(
-        "SELECT id, conversation_id, role, content, swipe_variants, swipe_index \
+        "SELECT id, conversation_id, role, content, swipe_variants, swipe_index, swipe_stats, swipe_reasoning, generation_status \
          FROM messages WHERE conversation_id = ?1 ORDER BY created_at ASC, rowid ASC"
     ).map_err(|e| e.to_string())?;
 
@@ -37,6 +89,9 @@ pub fn get_messages(app: AppHandle, chat_id: String) -> Result<Vec<DbMessage>, S
             content: row.get(3)?,
             swipe_variants: row.get(4)?,
             swipe_index: row.get(5)?,
+            swipe_stats: row.get(6)?,
+            swipe_reasoning: row.get(7)?,
+            generation_status: row.get(8)?,
         })
     }).map_err(|e| e.to_string())?;
 
@@ -48,21 +103,39 @@ pub fn get_messages(app: AppHandle, chat_id: String) -> Result<Vec<DbMessage>, S
 /// Appends a new message to the chat log.
 /// Automatically updates the conversation title based on the user's first input.
 /// The initial content is stored both in `content` and as the first entry in `swipe_variants`.
+/// `stats`, `reasoning` and `generation_id` come from the generation that
+/// produced it, if any; the ID links the recorded request to the message.
 #[tauri::command]
-pub fn add_message(app: AppHandle, chat_id: String, role: String, content: String) -> Result<(), String> {
+pub fn add_message(
+    app: AppHandle,
+    chat_id: String,
+    role: String,
+    content: String,
+    stats: Option<GenerationStats>,
+    reasoning: Option<String>,
+    generation_id: Option<String>,
+) -> Result<(), String> {
     let conn = crate::database::get_connection(&app)?;
 
     let msg_id = Uuid::new_v4().to_string();
     // Store the initial content as the first (and only) swipe variant.
     let initial_variants = serde_json::to_string(&vec![&content])
         .map_err(|e| e.to_string())?;
+    let initial_stats = serde_json::to_string(&vec![stats])
+        .map_err(|e| e.to_string())?;
+    let initial_reasoning = serde_json::to_string(&vec![reasoning])
+        .map_err(|e| e.to_string())?;
 
     conn.execute(
-        "INSERT INTO messages (id, conversation_id, role, content, swipe_variants, swipe_index) \
-         VALUES (?1, ?2, ?3, ?4, ?5, 0)",
-        rusqlite::params![msg_id, chat_id, role, content, initial_variants],
+        "INSERT INTO messages (id, conversation_id, role, content, swipe_variants, swipe_index, swipe_stats, swipe_reasoning) \
+         VALUES (?1, ?2, ?3, ?4, ?5, 0, ?6, ?7)",
+        rusqlite::params![msg_id, chat_id, role, content, initial_variants, initial_stats, initial_reasoning],
     ).map_err(|e| e.to_string())?;
 
+    if let Some(generation_id) = &generation_id {
+        generation_requests::link_request(&app, generation_id, &msg_id, Some(0))?;
+    }
+
     // Auto-titling logic: use the first user message as the conversation title.
     if role == "user" {
         let count: i64 = conn.query_row(
@@ -100,33 +173,313 @@ pub fn add_message(app: AppHandle, chat_id: String, role: String, content: Strin
 /// The new variant is added to the `swipe_variants` array and becomes the active one.
 /// Returns the new swipe_index so the frontend can update its local state.
 #[tauri::command]
-pub fn add_swipe_variant(app: AppHandle, message_id: String, content: String) -> Result<i64, String> {
-    let conn = get_connection(&app)?;
+pub fn add_swipe_variant(
+    app: AppHandle,
+    message_id: String,
+    content: String,
+    stats: Option<GenerationStats>,
+    reasoning: Option<String>,
+    generation_id: Option<String>,
+) -> Result<i64, String> {
+    let variant = NewVariant { content, stats, reasoning, generation_id };
+    add_swipe_variants(&app, &message_id, &[variant])?
+        .pop()
+        .ok_or_else(|| "Variant was not saved".to_string())
+}
 
+/// A finished reply to append to a message; see add_swipe_variants.
+pub(crate) struct NewVariant {
+    pub content: String,
+    pub stats: Option<GenerationStats>,
+    pub reasoning: Option<String>,
+    /// The request it was generated by, to link for the prompt inspector.
+    pub generation_id: Option<String>,
+}
+
+/// Appends `variants` to a message in order, all or none, the last one
+/// becoming the active one. Returns their swipe indices.
+pub(crate) fn add_swipe_variants(
+    app: &AppHandle,
+    message_id: &str,
+    variants: &[NewVariant],
+) -> Result<Vec<i64>, String> {
+    let mut conn = get_connection(app)?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let mut indices = Vec::with_capacity(variants.len());
+    for variant in variants {
+        let index = append_swipe_variant(
+            &tx,
+            message_id,
+            &variant.content,
+            variant.stats.as_ref(),
+            variant.reasoning.clone(),
+        )?;
+        if let Some(generation_id) = &variant.generation_id {
+            generation_requests::link_request_in(&tx, generation_id, message_id, Some(index))?;
+        }
+        indices.push(index);
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(indices)
+}
+
+/// Shared by add_swipe_variants and backend-owned generations. Reads and
+/// rewrites the variant arrays, so `conn` should be a transaction.
+fn append_swipe_variant(
+    conn: &Connection,
+    message_id: &str,
+    content: &str,
+    stats: Option<&GenerationStats>,
+    reasoning: Option<String>,
+) -> Result<i64, String> {
     // Fetch current variants JSON.
-    let current_variants_json: String = conn.query_row(
-        "SELECT swipe_variants FROM messages WHERE id = ?1",
-        params![message_id],
-        |row| row.get(0),
-    ).map_err(|e| e.to_string())?;
+    let (current_variants_json, current_stats_json, current_reasoning_json): (String, String, String) =
+        conn.query_row(
+            "SELECT swipe_variants, swipe_stats, swipe_reasoning FROM messages WHERE id = ?1",
+            params![message_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        ).map_err(|e| e.to_string())?;
 
     let mut variants: Vec<String> = serde_json::from_str(&current_variants_json)
         .unwrap_or_default();
 
-    variants.push(content.clone());
+    variants.push(content.to_string());
     let new_index = (variants.len() as i64) - 1;
 
     let updated_json = serde_json::to_string(&variants)
         .map_err(|e| e.to_string())?;
+    let stats_value = serde_json::to_value(stats).map_err(|e| e.to_string())?;
+    let updated_stats = set_variant_slot(&current_stats_json, new_index as usize, stats_value)?;
+    let updated_reasoning = set_variant_slot(
+        &current_reasoning_json,
+        new_index as usize,
+        serde_json::Value::from(reasoning),
+    )?;
 
     conn.execute(
-        "UPDATE messages SET content = ?1, swipe_variants = ?2, swipe_index = ?3 WHERE id = ?4",
-        params![content, updated_json, new_index, message_id],
+        "UPDATE messages SET content = ?1, swipe_variants = ?2, swipe_index = ?3, swipe_stats = ?4, swipe_reasoning = ?5 \
+         WHERE id = ?6",
+        params![content, updated_json, new_index, updated_stats, updated_reasoning, message_id],
     ).map_err(|e| e.to_string())?;
 
     Ok(new_index)
 }
 
+/// Reads the role and active text of a single message.
+pub(crate) fn get_message_content(app: &AppHandle, message_id: &str) -> Result<(String, String), String> {
+    let conn = get_connection(app)?;
+    conn.query_row(
+        "SELECT role, content FROM messages WHERE id = ?1",
+        params![message_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).map_err(|e| e.to_string())
+}
+
+/// Creates an empty assistant message for a backend-owned generation to stream
+/// into. Returns the new message's ID.
+pub(crate) fn start_streaming_message(app: &AppHandle, chat_id: &str) -> Result<String, String> {
+    let conn = get_connection(app)?;
+    let msg_id = Uuid::new_v4().to_string();
+
+    conn.execute(
+        "INSERT INTO messages (id, conversation_id, role, content, swipe_variants, swipe_index, \
+         swipe_stats, swipe_reasoning, generation_status) \
+         VALUES (?1, ?2, 'assistant', '', '[\"\"]', 0, '[null]', '[null]', ?3)",
+        params![msg_id, chat_id, GenerationStatus::Streaming.as_str()],
+    ).map_err(|e| e.to_string())?;
+
+    Ok(msg_id)
+}
+
+/// Appends an empty swipe variant for a backend-owned generation to stream
+/// into. Returns its index.
+pub(crate) fn start_streaming_variant(app: &AppHandle, message_id: &str) -> Result<i64, String> {
+    let mut conn = get_connection(app)?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let index = append_swipe_variant(&tx, message_id, "", None, None)?;
+    set_generation_status(&tx, message_id, GenerationStatus::Streaming)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(index)
+}
+
+/// Saves the text streamed into a variant so far. `content` only follows along
+/// while that variant is the active one, so swiping away mid-stream still works.
+pub(crate) fn save_variant_text(
+    app: &AppHandle,
+    message_id: &str,
+    index: i64,
+    content: &str,
+) -> Result<(), String> {
+    let mut conn = get_connection(app)?;
+    // Read and written in one go, so a swipe or an edit in between isn't lost.
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    let (variants_json, swipe_index): (String, i64) = tx.query_row(
+        "SELECT swipe_variants, swipe_index FROM messages WHERE id = ?1",
+        params![message_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).map_err(|e| e.to_string())?;
+
+    let updated_json = set_variant_text(&variants_json, index as usize, content)?;
+
+    if swipe_index == index {
+        tx.execute(
+            "UPDATE messages SET content = ?1, swipe_variants = ?2 WHERE id = ?3",
+            params![content, updated_json, message_id],
+        ).map_err(|e| e.to_string())?;
+    } else {
+        tx.execute(
+            "UPDATE messages SET swipe_variants = ?1 WHERE id = ?2",
+            params![updated_json, message_id],
+        ).map_err(|e| e.to_string())?;
+    }
+
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Records the outcome of a backend-owned generation on its variant.
+/// The text itself has already been written through save_variant_text.
+pub(crate) fn finish_variant(
+    app: &AppHandle,
+    message_id: &str,
+    index: i64,
+    stats: Option<&GenerationStats>,
+    reasoning: Option<String>,
+    status: GenerationStatus,
+) -> Result<(), String> {
+    let mut conn = get_connection(app)?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    let (stats_json, reasoning_json): (String, String) = tx.query_row(
+        "SELECT swipe_stats, swipe_reasoning FROM messages WHERE id = ?1",
+        params![message_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).map_err(|e| e.to_string())?;
+
+    let stats_value = serde_json::to_value(stats).map_err(|e| e.to_string())?;
+    let updated_stats = set_variant_slot(&stats_json, index as usize, stats_value)?;
+    let updated_reasoning = set_variant_slot(&reasoning_json, index as usize, serde_json::Value::from(reasoning))?;
+
+    tx.execute(
+        "UPDATE messages SET swipe_stats = ?1, swipe_reasoning = ?2, generation_status = ?3 WHERE id = ?4",
+        params![updated_stats, updated_reasoning, status.as_str(), message_id],
+    ).map_err(|e| e.to_string())?;
+
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Saves a continued reply into the message's active variant, together with
+/// the continuation's stats and reasoning (appended to what the variant
+/// already had), and ties the continuation's request to it.
+pub(crate) fn save_continuation(
+    app: &AppHandle,
+    message_id: &str,
+    content: &str,
+    stats: Option<&GenerationStats>,
+    reasoning: &str,
+    generation_id: &str,
+) -> Result<(), String> {
+    let mut conn = get_connection(app)?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    let (variants_json, swipe_index, stats_json, reasoning_json): (String, i64, String, String) =
+        tx.query_row(
+            "SELECT swipe_variants, swipe_index, swipe_stats, swipe_reasoning FROM messages WHERE id = ?1",
+            params![message_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        ).map_err(|e| e.to_string())?;
+    let index = swipe_index as usize;
+
+    let mut variants: Vec<String> = serde_json::from_str(&variants_json).unwrap_or_default();
+    if let Some(slot) = variants.get_mut(index) {
+        *slot = content.to_string();
+    }
+    let stats_value = serde_json::to_value(stats).map_err(|e| e.to_string())?;
+    let earlier: Vec<Option<String>> = serde_json::from_str(&reasoning_json).unwrap_or_default();
+    let reasoning = match earlier.get(index).cloned().flatten().filter(|r| !r.is_empty()) {
+        Some(earlier) if !reasoning.is_empty() => Some(format!("{}\n\n{}", earlier, reasoning)),
+        Some(earlier) => Some(earlier),
+        None => Some(reasoning.to_string()).filter(|r| !r.is_empty()),
+    };
+
+    tx.execute(
+        "UPDATE messages SET content = ?1, swipe_variants = ?2, swipe_stats = ?3, swipe_reasoning = ?4 \
+         WHERE id = ?5",
+        params![
+            content,
+            serde_json::to_string(&variants).map_err(|e| e.to_string())?,
+            set_variant_slot(&stats_json, index, stats_value)?,
+            set_variant_slot(&reasoning_json, index, serde_json::Value::from(reasoning))?,
+            message_id
+        ],
+    ).map_err(|e| e.to_string())?;
+    // The variant now reads as the continued text, so the continuation is
+    // the request to show for it.
+    generation_requests::link_request_in(&tx, generation_id, message_id, Some(swipe_index))?;
+
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Undoes start_streaming_message / start_streaming_variant for a generation
+/// that failed before producing any text, so no empty reply is left behind.
+pub(crate) fn discard_variant(app: &AppHandle, message_id: &str, index: i64) -> Result<(), String> {
+    let mut conn = get_connection(app)?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    let (variants_json, stats_json, reasoning_json, swipe_index): (String, String, String, i64) =
+        tx.query_row(
+            "SELECT swipe_variants, swipe_stats, swipe_reasoning, swipe_index FROM messages WHERE id = ?1",
+            params![message_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        ).map_err(|e| e.to_string())?;
+
+    let mut variants: Vec<String> = serde_json::from_str(&variants_json).unwrap_or_default();
+    if variants.len() <= 1 {
+        tx.execute("DELETE FROM messages WHERE id = ?1", params![message_id])
+            .map_err(|e| e.to_string())?;
+        return tx.commit().map_err(|e| e.to_string());
+    }
+
+    let index = index as usize;
+    let remove_slot = |json: &str| -> Result<String, String> {
+        let mut slots: Vec<serde_json::Value> = serde_json::from_str(json).unwrap_or_default();
+        if index < slots.len() {
+            slots.remove(index);
+        }
+        serde_json::to_string(&slots).map_err(|e| e.to_string())
+    };
+
+    if index < variants.len() {
+        variants.remove(index);
+    }
+    let new_index = (swipe_index as usize).min(variants.len() - 1);
+    let active_content = variants[new_index].clone();
+
+    tx.execute(
+        "UPDATE messages SET content = ?1, swipe_variants = ?2, swipe_stats = ?3, swipe_reasoning = ?4, \
+         swipe_index = ?5, generation_status = ?6 WHERE id = ?7",
+        params![
+            active_content,
+            serde_json::to_string(&variants).map_err(|e| e.to_string())?,
+            remove_slot(&stats_json)?,
+            remove_slot(&reasoning_json)?,
+            new_index as i64,
+            GenerationStatus::Complete.as_str(),
+            message_id
+        ],
+    ).map_err(|e| e.to_string())?;
+
+    tx.commit().map_err(|e| e.to_string())
+}
+
+fn set_generation_status(conn: &Connection, message_id: &str, status: GenerationStatus) -> Result<(), String> {
+    conn.execute(
+        "UPDATE messages SET generation_status = ?1 WHERE id = ?2",
+        params![status.as_str(), message_id],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 /// Navigates to a specific variant by index without creating a new one.
 /// Updates both `swipe_index` and `content` (so the rest of the app always reads the active text).
 #[tauri::command]
@@ -207,7 +560,7 @@ pub fn get_messages_page(app: AppHandle, chat_id: String, limit: i64, offset: i6
     // second-level resolution (relevant e.g. right after cloning a chat,
     // where many messages get inserted within the same second).
     let mut stmt = conn.prepare(
-        "SELECT id, conversation_id, role, content, swipe_variants, swipe_index \
+        "SELECT id, conversation_id, role, content, swipe_variants, swipe_index, swipe_stats, swipe_reasoning, generation_status \
          FROM messages WHERE conversation_id = ?1 \
          ORDER BY created_at DESC, rowid DESC \
          LIMIT ?2 OFFSET ?3"
@@ -221,6 +574,9 @@ pub fn get_messages_page(app: AppHandle, chat_id: String, limit: i64, offset: i6
             content: row.get(3)?,
             swipe_variants: row.get(4)?,
             swipe_index: row.get(5)?,
+            swipe_stats: row.get(6)?,
+            swipe_reasoning: row.get(7)?,
+            generation_status: row.get(8)?,
         })
     }).map_err(|e| e.to_string())?;
 
@@ -231,4 +587,17 @@ pub fn get_messages_page(app: AppHandle, chat_id: String, limit: i64, offset: i6
     list.reverse();
     
     Ok(list)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variant_text_past_the_end_keeps_the_list_readable() {
+        // A variant whose index moved past the end, e.g. after another
+        // streaming variant of the message was discarded.
+        let json = set_variant_text(r#"["first","second"]"#, 3, "fourth").unwrap();
+        let variants: Vec<String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(variants, ["first", "second", "", "fourth"]);
+    }
+}
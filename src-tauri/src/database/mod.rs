@@ -8,6 +8,10 @@ pub mod settings;
 pub mod characters;
 pub mod world_info;
 pub mod roles;
+pub mod generation_requests;
+pub(crate) mod embeddings;
+pub mod memory;
+pub mod character_memories;
 
 const DB_FILENAME: &str = "ryokan.db";
 
@@ -71,6 +75,9 @@ pub fn init_db(app: &AppHandle) -> Result<(), String> {
             content TEXT,
             swipe_variants TEXT NOT NULL DEFAULT '[]',
             swipe_index INTEGER NOT NULL DEFAULT 0,
+            swipe_stats TEXT NOT NULL DEFAULT '[]',
+            swipe_reasoning TEXT NOT NULL DEFAULT '[]',
+            generation_status TEXT NOT NULL DEFAULT 'complete',
             created_at DATETIME DEFAULT {utc_now},
             FOREIGN KEY (conversation_id) REFERENCES conversations(id) ON DELETE CASCADE
         );
@@ -120,8 +127,106 @@ pub fn init_db(app: &AppHandle) -> Result<(), String> {
             name        TEXT NOT NULL,
             description TEXT NOT NULL DEFAULT '',
             entries     TEXT NOT NULL DEFAULT '[]',
+            scan_depth  INTEGER,
+            token_budget INTEGER,
+            semantic_threshold REAL,
             created_at  DATETIME DEFAULT {utc_now}
         );
+
+        -- Request bodies sent for generations (prompt inspector). message_id and
+        -- variant are filled in once the reply is saved as a swipe variant.
+        CREATE TABLE IF NOT EXISTS generation_requests (
+            generation_id TEXT PRIMARY KEY,
+            message_id    TEXT,
+            variant       INTEGER,
+            request       TEXT NOT NULL,
+            created_at    DATETIME DEFAULT {utc_now},
+            FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_generation_requests_message ON generation_requests(message_id, variant);
+        CREATE INDEX IF NOT EXISTS idx_generation_requests_created_at ON generation_requests(created_at);
+
+        -- Sticky / cooldown windows of world info entries, per chat. Positions
+        -- are message indices in the chat, so clones can copy the rows that
+        -- happened before their cut-off.
+        CREATE TABLE IF NOT EXISTS world_info_timed_effects (
+            chat_id        TEXT NOT NULL,
+            book_id        TEXT NOT NULL,
+            entry_id       TEXT NOT NULL,
+            activated_at   INTEGER NOT NULL,
+            sticky_until   INTEGER NOT NULL,
+            cooldown_until INTEGER NOT NULL,
+            PRIMARY KEY (chat_id, book_id, entry_id, activated_at),
+            FOREIGN KEY (chat_id) REFERENCES conversations(id) ON DELETE CASCADE
+        );
+
+        -- World infos active beyond a character's own: in every chat, in one
+        -- chat, or with one persona. Book IDs aren't foreign keys so built-in
+        -- books can be linked; see world_info::links.
+        CREATE TABLE IF NOT EXISTS global_world_infos (
+            world_info_id TEXT PRIMARY KEY,
+            position      INTEGER NOT NULL DEFAULT 0
+        );
+
+        CREATE TABLE IF NOT EXISTS chat_world_infos (
+            chat_id       TEXT NOT NULL,
+            world_info_id TEXT NOT NULL,
+            position      INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (chat_id, world_info_id),
+            FOREIGN KEY (chat_id) REFERENCES conversations(id) ON DELETE CASCADE
+        );
+
+        CREATE TABLE IF NOT EXISTS role_world_infos (
+            role_id       TEXT NOT NULL,
+            world_info_id TEXT NOT NULL,
+            position      INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (role_id, world_info_id),
+            FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE
+        );
+
+        -- Embedding vectors (little-endian f32) of world info entry contents
+        -- and memory chunks, by SHA-256 of the content and the model that
+        -- produced them.
+        CREATE TABLE IF NOT EXISTS embeddings (
+            content_hash TEXT NOT NULL,
+            model        TEXT NOT NULL,
+            vector       BLOB NOT NULL,
+            created_at   DATETIME DEFAULT {utc_now},
+            PRIMARY KEY (content_hash, model)
+        );
+
+        -- Summarized messages of a chat in fixed-size chunks with their
+        -- vectors, recalled by similarity to the latest turn; see memory.rs.
+        CREATE TABLE IF NOT EXISTS memory_chunks (
+            chat_id          TEXT NOT NULL,
+            position         INTEGER NOT NULL,
+            first_message_id TEXT NOT NULL,
+            last_message_id  TEXT NOT NULL,
+            content          TEXT NOT NULL,
+            content_hash     TEXT NOT NULL,
+            model            TEXT NOT NULL,
+            vector           BLOB NOT NULL,
+            created_at       DATETIME DEFAULT {utc_now},
+            PRIMARY KEY (chat_id, position),
+            FOREIGN KEY (chat_id) REFERENCES conversations(id) ON DELETE CASCADE
+        );
+
+        -- What characters remember across chats, for every persona (role_id
+        -- NULL) or one. No foreign keys: built-in characters aren't stored,
+        -- so delete_character / delete_role clean up instead.
+        CREATE TABLE IF NOT EXISTS character_memories (
+            id             TEXT PRIMARY KEY,
+            character_id   TEXT NOT NULL,
+            role_id        TEXT,
+            content        TEXT NOT NULL,
+            source_chat_id TEXT,
+            enabled        INTEGER NOT NULL DEFAULT 1,
+            created_at     DATETIME DEFAULT {utc_now},
+            updated_at     DATETIME DEFAULT {utc_now}
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_character_memories_character ON character_memories(character_id);
     "#,
         utc_now = UTC_NOW
     );
@@ -154,5 +259,48 @@ pub fn init_db(app: &AppHandle) -> Result<(), String> {
         "ALTER TABLE conversations ADD COLUMN cloned_from_title TEXT;"
     );
 
+    // ── Migration: per-variant generation stats (token usage, timing) ──
+    // A JSON array parallel to swipe_variants; null where a variant has none
+    // (user messages, greetings, replies written before this column existed).
+    let _ = conn.execute_batch(
+        "ALTER TABLE messages ADD COLUMN swipe_stats TEXT NOT NULL DEFAULT '[]';"
+    );
+
+    // ── Migration: per-variant reasoning ("thinking") text ──
+    // Same parallel-array layout as swipe_stats. Kept out of `content` so it
+    // never ends up in a prompt unless deliberately added back.
+    let _ = conn.execute_batch(
+        "ALTER TABLE messages ADD COLUMN swipe_reasoning TEXT NOT NULL DEFAULT '[]';"
+    );
+
+    // ── Migration: per-lorebook activation settings ──
+    // NULL = engine default (see database::world_info::activation).
+    let _ = conn.execute_batch(
+        "ALTER TABLE world_infos ADD COLUMN scan_depth INTEGER;"
+    );
+    let _ = conn.execute_batch(
+        "ALTER TABLE world_infos ADD COLUMN token_budget INTEGER;"
+    );
+    let _ = conn.execute_batch(
+        "ALTER TABLE world_infos ADD COLUMN semantic_threshold REAL;"
+    );
+
+    // ── Migration: status of backend-owned generations ──
+    let _ = conn.execute_batch(
+        "ALTER TABLE messages ADD COLUMN generation_status TEXT NOT NULL DEFAULT 'complete';"
+    );
+    // Nothing can still be streaming at startup: whatever was mid-stream when the
+    // app last quit is kept, but flagged as cut off.
+    conn.execute(
+        "UPDATE messages SET generation_status = 'interrupted' WHERE generation_status = 'streaming'",
+        [],
+    ).map_err(|e| format!("Failed to reset interrupted generations: {}", e))?;
+
+    // Once per start rather than on every generation. Only housekeeping, so
+    // a failure mustn't keep the app from starting.
+    if let Err(e) = generation_requests::prune_expired(app) {
+        eprintln!("Failed to prune recorded requests: {}", e);
+    }
+
     Ok(())
 }
\ No newline at end of file
@@ -2,7 +2,7 @@ use tauri::AppHandle;
 use rusqlite::params;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use crate::database::get_connection;
+use crate::database::{character_memories, get_connection};
 use base64::{engine::general_purpose, Engine as _};
 use image::ImageFormat;
 use webp::{Encoder, WebPMemory};
@@ -112,6 +112,33 @@ pub fn get_roles(app: AppHandle) -> Result<Vec<DbRole>, String> {
     Ok(list)
 }
 
+/// Looks up a single role; None if no role with that ID exists (e.g. one of
+/// the frontend's built-in default roles, which aren't stored).
+pub(crate) fn get_role(app: &AppHandle, id: &str) -> Result<Option<DbRole>, String> {
+    let conn = get_connection(app)?;
+
+    let result = conn.query_row(
+        "SELECT id, name, bio, pronouns, avatar, created_at FROM roles WHERE id = ?1",
+        params![id],
+        |row| {
+            Ok(DbRole {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                bio: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                pronouns: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+                avatar: row.get(4)?,
+                created_at: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+            })
+        },
+    );
+
+    match result {
+        Ok(role) => Ok(Some(role)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
 /// Inserts a new role. Avatar processing runs on a background thread.
 /// Returns the new UUID so the frontend can update its optimistic entry.
 #[tauri::command]
@@ -204,5 +231,6 @@ pub fn delete_role(app: AppHandle, id: String) -> Result<(), String> {
     let conn = get_connection(&app)?;
     conn.execute("DELETE FROM roles WHERE id = ?1", params![id])
         .map_err(|e| e.to_string())?;
+    character_memories::delete_memories_of_role(&conn, &id)?;
     Ok(())
 }
\ No newline at end of file
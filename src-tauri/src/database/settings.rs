@@ -47,4 +47,22 @@ pub fn save_setting(app: AppHandle, key: String, value: String) -> Result<(), St
     ).map_err(|e| e.to_string())?;
     
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Reads a single setting, for backend code that needs a user preference.
+/// Returns None if it was never saved.
+pub(crate) fn get_setting(app: &AppHandle, key: &str) -> Result<Option<String>, String> {
+    let conn = get_connection(app)?;
+
+    let result = conn.query_row(
+        "SELECT value FROM settings WHERE key = ?1",
+        params![key],
+        |row| row.get(0),
+    );
+
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
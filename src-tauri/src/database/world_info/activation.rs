@@ -0,0 +1,581 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use super::matcher::{Hits, KeyMatcher};
+use super::{SelectiveLogic, WorldInfoEntry};
+use crate::tokenizer::count_tokens;
+
+/// Scan depth of books that don't set one; what the frontend always matched against.
+pub const DEFAULT_SCAN_DEPTH: u32 = 10;
+
+/// A lorebook's entries and settings, as the engine needs them.
+pub(crate) struct Lorebook<'a> {
+    pub id: &'a str,
+    pub entries: &'a [WorldInfoEntry],
+    /// Built from `entries`; see matcher::cached.
+    pub matcher: Arc<KeyMatcher>,
+    pub scan_depth: u32,
+    pub token_budget: Option<u32>,
+    /// Minimum similarity for `similarity` to trigger an entry; None = keywords only.
+    pub semantic_threshold: Option<f32>,
+    /// Similarity of each entry's content to the recent messages, by entry
+    /// index; empty or None where it couldn't be computed.
+    pub similarity: Vec<Option<f32>>,
+}
+
+/// Where a chat stands with its entries' timed effects; see timed_effects.
+#[derive(Default)]
+pub(crate) struct Timeline {
+    /// Messages before the one being generated.
+    pub position: u32,
+    /// Entry IDs by book ID that stay active without their keys.
+    pub sticky: HashMap<String, HashSet<String>>,
+    /// Entry IDs by book ID that can't trigger.
+    pub cooldown: HashMap<String, HashSet<String>>,
+}
+
+/// FNV-1a over `parts` on top of `seed`, finished with splitmix64 so that
+/// close inputs give unrelated values.
+fn mix(seed: u64, parts: &[&str]) -> u64 {
+    let mut h = seed ^ 0xcbf2_9ce4_8422_2325;
+    for part in parts {
+        for byte in part.bytes().chain([0]) {
+            h ^= u64::from(byte);
+            h = h.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^ (h >> 31)
+}
+
+/// Default seed for probability rolls: the same for every attempt at the same
+/// message of a chat, so rebuilding a prompt gives the same entries.
+pub(crate) fn chat_seed(chat_id: &str, position: u32) -> u64 {
+    mix(u64::from(position), &[chat_id])
+}
+
+/// Whether a triggered entry passes its probability. Each entry rolls on its
+/// own, so the result doesn't depend on which other entries triggered.
+fn passes_probability(seed: u64, book: &Lorebook, entry: &WorldInfoEntry) -> bool {
+    entry.probability >= 100 || mix(seed, &[book.id, &entry.id]) % 100 < u64::from(entry.probability)
+}
+
+fn contains_entry(set: &HashMap<String, HashSet<String>>, book: &Lorebook, entry: &WorldInfoEntry) -> bool {
+    set.get(book.id).is_some_and(|ids| ids.contains(&entry.id))
+}
+
+/// An entry that triggered through its keys and has a sticky or cooldown
+/// window starting now.
+pub(crate) struct Started<'a> {
+    pub book_id: &'a str,
+    pub entry: &'a WorldInfoEntry,
+}
+
+/// Why an entry became a candidate.
+#[derive(Serialize, Clone, PartialEq, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Trigger {
+    /// This primary key matched (secondary keys, if any, agreed).
+    Key { key: String },
+    NoKeys,
+    Constant,
+    Sticky,
+    /// The content is this similar to the recent messages.
+    Semantic { similarity: f32 },
+}
+
+/// What became of an entry, the last check it failed or `Activated`.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryStatus {
+    Activated,
+    NotTriggered,
+    /// Switched off or without content.
+    Disabled,
+    Delayed,
+    Cooldown,
+    ProbabilityFailed,
+    /// Another entry of its inclusion group was picked.
+    LostToGroup,
+    /// Didn't fit the rest of its book's token budget.
+    OverBudget,
+}
+
+/// How `activate` got to its decision about one entry.
+pub(crate) struct EntryTrace {
+    /// Indices into the books and their entries.
+    pub book: usize,
+    pub entry: usize,
+    pub status: EntryStatus,
+    pub trigger: Option<Trigger>,
+    pub scan_depth: u32,
+    /// Recursion step it triggered in; 0 = the messages themselves.
+    pub step: Option<u32>,
+    pub tokens: Option<u32>,
+}
+
+/// What `activate` picked, and why, for every entry of every book.
+pub(crate) struct Activation<'a> {
+    pub entries: Vec<&'a WorldInfoEntry>,
+    pub started: Vec<Started<'a>>,
+    pub trace: Vec<EntryTrace>,
+}
+
+/// The text keys are matched against: the latest messages joined, followed by
+/// the content of entries activated so far when scanning recursively, plus a
+/// lowercased copy for case-insensitive keys. Built once per scan depth and
+/// step, and run through each book's matcher once.
+struct ScanBuffer<'a> {
+    messages: &'a [&'a str],
+    recursion: Vec<&'a str>,
+    windows: HashMap<u32, (String, String)>,
+    /// By book index and scan depth.
+    hits: HashMap<(usize, u32), Hits>,
+}
+
+impl<'a> ScanBuffer<'a> {
+    fn scan(&mut self, book: usize, matcher: &KeyMatcher, depth: u32) -> (&str, &Hits) {
+        let (messages, recursion) = (self.messages, &self.recursion);
+        let window = self.windows.entry(depth).or_insert_with(|| {
+            let from = messages.len().saturating_sub(depth as usize);
+            let text = messages[from..]
+                .iter()
+                .chain(recursion)
+                .copied()
+                .collect::<Vec<_>>()
+                .join("\n");
+            let lower = text.to_lowercase();
+            (text, lower)
+        });
+        let hits = self.hits.entry((book, depth)).or_insert_with(|| matcher.scan(window));
+        (&window.0, hits)
+    }
+
+    fn recurse(&mut self, content: impl IntoIterator<Item = &'a str>) {
+        self.recursion.extend(content);
+        self.windows.clear();
+        self.hits.clear();
+    }
+}
+
+/// Whether entry `index` of a book is triggered by the scanned text, and
+/// through which primary key. Entries without any keys are always triggered.
+fn find_trigger(
+    matcher: &KeyMatcher,
+    index: usize,
+    entry: &WorldInfoEntry,
+    (text, hits): (&str, &Hits),
+) -> Option<Trigger> {
+    if !matcher.has_keys(index)? {
+        return Some(Trigger::NoKeys);
+    }
+    let key = matcher.primary_match(index, entry, text, hits)?;
+
+    let mut secondary = matcher.secondary_matches(index, entry, text, hits).peekable();
+    if secondary.peek().is_some() {
+        let passed = match entry.selective_logic {
+            SelectiveLogic::AndAny => secondary.any(|hit| hit),
+            SelectiveLogic::AndAll => secondary.all(|hit| hit),
+            SelectiveLogic::NotAny => !secondary.any(|hit| hit),
+            SelectiveLogic::NotAll => !secondary.all(|hit| hit),
+        };
+        if !passed {
+            return None;
+        }
+    }
+    Some(Trigger::Key { key: key.to_string() })
+}
+
+/// Whether entry `index` is close enough to the recent messages, in books
+/// with semantic activation.
+fn semantic_trigger(book: &Lorebook, index: usize) -> Option<Trigger> {
+    let threshold = book.semantic_threshold?;
+    let similarity = book.similarity.get(index).copied().flatten()?;
+    (similarity >= threshold).then_some(Trigger::Semantic { similarity })
+}
+
+/// Picks the entries of `books` that `messages` (oldest first, a pending user
+/// message included) trigger. Each book is scanned to its own depth and keeps
+/// to its own token budget, counted with `model`'s tokenizer; an entry that
+/// doesn't fit the remaining budget is left out.
+///
+/// With `max_recursion` > 0, the content of newly activated entries is added
+/// to the scanned text and the remaining entries are checked again, up to that
+/// many extra steps or until nothing new activates.
+///
+/// In books with a semantic threshold, entries whose similarity reaches it
+/// trigger as if a key had matched. Key-less entries of such books trigger
+/// only that way instead of always.
+///
+/// Sticky entries of `timeline` are active without their keys, entries on
+/// cooldown or still delayed can't trigger. Triggered entries then roll their
+/// probability with `seed`, and each inclusion group keeps its highest
+/// weighted entry (sticky ones first, ties go to the earlier entry); a group
+/// that got an entry in one step takes no more in later ones.
+///
+/// The result is sorted by the entries' order; equal orders stay in
+/// activation order: by step, then book order, then entry order.
+pub(crate) fn activate<'a>(
+    books: &[Lorebook<'a>],
+    messages: &'a [&'a str],
+    model: &str,
+    max_recursion: u32,
+    timeline: &Timeline,
+    seed: u64,
+) -> Activation<'a> {
+    let mut scan = ScanBuffer {
+        messages,
+        recursion: Vec::new(),
+        windows: HashMap::new(),
+        hits: HashMap::new(),
+    };
+    let mut used = vec![0; books.len()];
+    // One per entry, book after book; an entry is done once it has a step.
+    let mut trace: Vec<EntryTrace> = books
+        .iter()
+        .enumerate()
+        .flat_map(|(b, book)| {
+            book.entries.iter().enumerate().map(move |(i, entry)| EntryTrace {
+                book: b,
+                entry: i,
+                status: if entry.enabled && !entry.content.trim().is_empty() {
+                    EntryStatus::NotTriggered
+                } else {
+                    EntryStatus::Disabled
+                },
+                trigger: None,
+                scan_depth: entry.scan_depth.unwrap_or(book.scan_depth),
+                step: None,
+                tokens: None,
+            })
+        })
+        .collect();
+    // Inclusion groups that already have their entry.
+    let mut filled_groups = HashSet::new();
+    let mut activated = Vec::new();
+    let mut started = Vec::new();
+
+    for step in 0..=max_recursion {
+        // (trace index, entry, sticky) of this step's entries, before groups and budgets.
+        let mut candidates = Vec::new();
+        for (t, state) in trace.iter_mut().enumerate() {
+            let book = &books[state.book];
+            let entry = &book.entries[state.entry];
+            if state.step.is_some() || state.status == EntryStatus::Disabled {
+                continue;
+            }
+            let trigger = if step == 0 && contains_entry(&timeline.sticky, book, entry) {
+                Trigger::Sticky
+            } else {
+                if step > 0 && entry.exclude_recursion {
+                    continue;
+                }
+                if timeline.position < entry.delay {
+                    state.status = EntryStatus::Delayed;
+                    continue;
+                }
+                if contains_entry(&timeline.cooldown, book, entry) {
+                    state.status = EntryStatus::Cooldown;
+                    continue;
+                }
+                if entry.constant {
+                    Trigger::Constant
+                } else {
+                    let scanned = scan.scan(state.book, &book.matcher, state.scan_depth);
+                    let keyword = match find_trigger(&book.matcher, state.entry, entry, scanned) {
+                        Some(Trigger::NoKeys) if book.semantic_threshold.is_some() => None,
+                        trigger => trigger,
+                    };
+                    match keyword.or_else(|| semantic_trigger(book, state.entry)) {
+                        Some(trigger) => trigger,
+                        None => continue,
+                    }
+                }
+            };
+            let sticky = trigger == Trigger::Sticky;
+            state.trigger = Some(trigger);
+            state.step = Some(step);
+            if !sticky && !passes_probability(seed, book, entry) {
+                state.status = EntryStatus::ProbabilityFailed;
+                continue;
+            }
+            candidates.push((t, entry, sticky));
+        }
+
+        let mut winners: HashMap<&str, usize> = HashMap::new();
+        for (n, &(_, entry, sticky)) in candidates.iter().enumerate() {
+            let group = entry.group.trim();
+            if group.is_empty() || filled_groups.contains(group) {
+                continue;
+            }
+            winners
+                .entry(group)
+                .and_modify(|best| {
+                    let (_, current, current_sticky) = candidates[*best];
+                    if (sticky, entry.group_weight) > (current_sticky, current.group_weight) {
+                        *best = n;
+                    }
+                })
+                .or_insert(n);
+        }
+
+        let mut found = Vec::new();
+        for (n, &(t, entry, sticky)) in candidates.iter().enumerate() {
+            let state = &mut trace[t];
+            let group = entry.group.trim();
+            if !group.is_empty() && winners.get(group) != Some(&n) {
+                state.status = EntryStatus::LostToGroup;
+                continue;
+            }
+            let b = state.book;
+            let tokens = count_tokens(entry.content.trim().to_string(), model.to_string());
+            state.tokens = Some(tokens);
+            if books[b].token_budget.is_some_and(|budget| used[b] + tokens > budget) {
+                state.status = EntryStatus::OverBudget;
+                continue;
+            }
+            used[b] += tokens;
+            state.status = EntryStatus::Activated;
+            if !group.is_empty() {
+                filled_groups.insert(group);
+            }
+            found.push(entry);
+            if !sticky && (entry.sticky > 0 || entry.cooldown > 0) {
+                started.push(Started { book_id: books[b].id, entry });
+            }
+        }
+
+        let mut recursable = found
+            .iter()
+            .filter(|e| !e.prevent_recursion)
+            .map(|e| e.content.trim())
+            .peekable();
+        let more = recursable.peek().is_some();
+        scan.recurse(recursable);
+        activated.extend(found);
+        if !more {
+            break;
+        }
+    }
+    activated.sort_by_key(|e| e.order);
+    Activation { entries: activated, started, trace }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    /// An enabled entry with `fields` on top of the defaults.
+    fn entry(id: &str, keys: &[&str], content: &str, fields: Value) -> WorldInfoEntry {
+        let mut value = json!({
+            "id": id,
+            "keys": keys,
+            "content": content,
+            "enabled": true,
+            "comment": "",
+            "position": "before",
+        });
+        value.as_object_mut().unwrap().extend(fields.as_object().unwrap().clone());
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn book<'a>(id: &'a str, entries: &'a [WorldInfoEntry]) -> Lorebook<'a> {
+        Lorebook {
+            id,
+            entries,
+            matcher: Arc::new(KeyMatcher::new(entries)),
+            scan_depth: DEFAULT_SCAN_DEPTH,
+            token_budget: None,
+            semantic_threshold: None,
+            similarity: Vec::new(),
+        }
+    }
+
+    fn ids(activation: &Activation) -> Vec<String> {
+        activation.entries.iter().map(|e| e.id.clone()).collect()
+    }
+
+    fn run(entries: &[WorldInfoEntry], messages: &[&str], max_recursion: u32) -> Vec<String> {
+        let books = [book("book", entries)];
+        ids(&activate(&books, messages, "", max_recursion, &Timeline::default(), 0))
+    }
+
+    #[test]
+    fn recursion_stops_at_the_limit() {
+        let entries = [
+            entry("a", &["dragon"], "The dragon guards the castle.", json!({})),
+            entry("b", &["castle"], "The castle stands by the lake.", json!({})),
+            entry("c", &["lake"], "The lake is frozen.", json!({})),
+        ];
+        let messages = ["A dragon appears!"];
+        assert_eq!(run(&entries, &messages, 0), ["a"]);
+        assert_eq!(run(&entries, &messages, 1), ["a", "b"]);
+        assert_eq!(run(&entries, &messages, 2), ["a", "b", "c"]);
+        assert_eq!(run(&entries, &messages, 10), ["a", "b", "c"]);
+
+        let books = [book("book", &entries)];
+        let activation = activate(&books, &messages, "", 2, &Timeline::default(), 0);
+        let steps: Vec<_> = activation.trace.iter().map(|t| t.step).collect();
+        assert_eq!(steps, [Some(0), Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn prevent_recursion_keeps_content_out_of_the_scan() {
+        let entries = [
+            entry("a", &["dragon"], "The dragon guards the castle.", json!({ "prevent_recursion": true })),
+            entry("b", &["castle"], "The castle stands by the lake.", json!({})),
+        ];
+        assert_eq!(run(&entries, &["A dragon appears!"], 3), ["a"]);
+    }
+
+    #[test]
+    fn exclude_recursion_only_triggers_on_messages() {
+        let entries = [
+            entry("a", &["dragon"], "The dragon guards the castle.", json!({})),
+            entry("b", &["castle"], "The castle stands by the lake.", json!({ "exclude_recursion": true })),
+        ];
+        assert_eq!(run(&entries, &["A dragon appears!"], 3), ["a"]);
+        assert_eq!(run(&entries, &["A dragon flies to the castle."], 3), ["a", "b"]);
+    }
+
+    #[test]
+    fn order_is_stable_across_runs() {
+        let entries = [
+            entry("late", &["sword"], "Swords are forged in the north.", json!({ "order": 5 })),
+            entry("first", &["sword"], "Every sword has a name.", json!({ "order": 1 })),
+            entry("tie_a", &["sword"], "Swords are expensive.", json!({ "order": 5 })),
+            entry("tie_b", &[], "Always here.", json!({ "order": 5 })),
+        ];
+        let expected = ["first", "late", "tie_a", "tie_b"];
+        for _ in 0..20 {
+            assert_eq!(run(&entries, &["She draws her sword."], 2), expected);
+        }
+    }
+
+    #[test]
+    fn applies_selective_logic() {
+        let logic = |logic: &str| {
+            [entry("e", &["king"], "The king is old.", json!({
+                "secondary_keys": ["crown", "throne"],
+                "selective_logic": logic,
+            }))]
+        };
+        let one = ["The king lost his crown."];
+        let both = ["The king sits on his throne, wearing his crown."];
+        let none = ["The king went hunting."];
+
+        let and_any = logic("and_any");
+        assert_eq!(run(&and_any, &one, 0), ["e"]);
+        assert!(run(&and_any, &none, 0).is_empty());
+
+        let and_all = logic("and_all");
+        assert!(run(&and_all, &one, 0).is_empty());
+        assert_eq!(run(&and_all, &both, 0), ["e"]);
+
+        let not_any = logic("not_any");
+        assert!(run(&not_any, &one, 0).is_empty());
+        assert_eq!(run(&not_any, &none, 0), ["e"]);
+
+        let not_all = logic("not_all");
+        assert_eq!(run(&not_all, &one, 0), ["e"]);
+        assert!(run(&not_all, &both, 0).is_empty());
+
+        // The primary key always has to match.
+        assert!(run(&and_any, &["A crown on a throne."], 0).is_empty());
+    }
+
+    #[test]
+    fn matches_regex_keys() {
+        let entries = [
+            entry("regex", &["/gr[ae]y wolf/"], "Wolves hunt in packs.", json!({})),
+            entry("flags", &["/^ELF/im"], "Elves live long.", json!({})),
+        ];
+        assert_eq!(run(&entries, &["A grey wolf howls."], 0), ["regex"]);
+        assert_eq!(run(&entries, &["A gray wolf howls."], 0), ["regex"]);
+        assert!(run(&entries, &["A great wolf howls."], 0).is_empty());
+        assert_eq!(run(&entries, &["Hello.\nelf lords arrive."], 0), ["flags"]);
+    }
+
+    #[test]
+    fn matches_case_and_whole_words() {
+        let whole = [entry("cat", &["cat"], "Cats are sacred here.", json!({ "match_whole_words": true }))];
+        assert_eq!(run(&whole, &["A cat sleeps."], 0), ["cat"]);
+        assert!(run(&whole, &["Don't concatenate strings."], 0).is_empty());
+
+        let partial = [entry("cat", &["cat"], "Cats are sacred here.", json!({}))];
+        assert_eq!(run(&partial, &["Don't concatenate strings."], 0), ["cat"]);
+
+        let sensitive = [entry("mars", &["Mars"], "Mars is red.", json!({ "case_sensitive": true }))];
+        assert_eq!(run(&sensitive, &["We flew to Mars."], 0), ["mars"]);
+        assert!(run(&sensitive, &["We had a mars bar."], 0).is_empty());
+
+        let insensitive = [entry("mars", &["Mars"], "Mars is red.", json!({}))];
+        assert_eq!(run(&insensitive, &["We had a MARS bar."], 0), ["mars"]);
+    }
+
+    #[test]
+    fn same_seed_rolls_the_same_entries() {
+        let entries: Vec<_> = (0..40)
+            .map(|i| entry(&format!("e{}", i), &["rain"], "It rains.", json!({ "probability": 50 })))
+            .collect();
+        let books = [book("book", &entries)];
+        let roll = |seed| ids(&activate(&books, &["The rain won't stop."], "", 0, &Timeline::default(), seed));
+
+        let first = roll(7);
+        assert_eq!(roll(7), first);
+        // A coin flip each: all or nothing would mean the seed isn't used.
+        assert!(!first.is_empty() && first.len() < entries.len());
+        assert_ne!(roll(8), first);
+        assert_eq!(chat_seed("chat", 3), chat_seed("chat", 3));
+        assert_ne!(chat_seed("chat", 3), chat_seed("chat", 4));
+    }
+
+    #[test]
+    fn inclusion_group_keeps_the_highest_weight() {
+        let entries = [
+            entry("light", &["storm"], "A light storm.", json!({ "group": "weather", "group_weight": 10 })),
+            entry("heavy", &["storm"], "A heavy storm.", json!({ "group": "weather", "group_weight": 90 })),
+            entry("medium", &["storm"], "A storm.", json!({ "group": " weather ", "group_weight": 50 })),
+            entry("loner", &["storm"], "Storms scare the horses.", json!({})),
+        ];
+        let books = [book("book", &entries)];
+        let activation = activate(&books, &["A storm is coming."], "", 0, &Timeline::default(), 0);
+        assert_eq!(ids(&activation), ["heavy", "loner"]);
+        let statuses: Vec<_> = activation.trace.iter().map(|t| t.status).collect();
+        assert_eq!(statuses, [
+            EntryStatus::LostToGroup,
+            EntryStatus::Activated,
+            EntryStatus::LostToGroup,
+            EntryStatus::Activated,
+        ]);
+
+        // Equal weights go to the earlier entry.
+        let tied = [
+            entry("first", &["storm"], "A storm.", json!({ "group": "weather" })),
+            entry("second", &["storm"], "Another storm.", json!({ "group": "weather" })),
+        ];
+        assert_eq!(run(&tied, &["A storm is coming."], 0), ["first"]);
+    }
+
+    #[test]
+    fn stale_matcher_neither_panics_nor_triggers_new_entries() {
+        let old = [entry("a", &["dragon"], "A dragon.", json!({}))];
+        let new = [
+            entry("a", &["dragon"], "A dragon.", json!({})),
+            entry("b", &["dragon"], "Another dragon.", json!({})),
+        ];
+        // The book was saved with a second entry, but this matcher predates it.
+        let stale = Lorebook { matcher: Arc::new(KeyMatcher::new(&old)), ..book("book", &new) };
+        let activation = activate(&[stale], &["A dragon!"], "", 0, &Timeline::default(), 0);
+        assert_eq!(ids(&activation), ["a"]);
+
+        // The cache doesn't hand out a matcher built from other entries.
+        crate::database::world_info::matcher::rebuild("stale-test", &old);
+        let cached = crate::database::world_info::matcher::cached("stale-test", &new);
+        assert_eq!(cached.has_keys(1), Some(true));
+        crate::database::world_info::matcher::forget("stale-test");
+    }
+}
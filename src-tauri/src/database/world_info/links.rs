@@ -0,0 +1,160 @@
+use rusqlite::{params, Connection, ToSql};
+use std::collections::HashSet;
+use tauri::AppHandle;
+
+use crate::database::get_connection;
+
+/// A place world infos can be attached to besides a character, and the link
+/// table that records it. Book IDs aren't foreign keys, so built-in books can
+/// be linked too; delete_world_info removes a deleted book's links.
+#[derive(Clone, Copy)]
+enum Scope {
+    /// Active in every chat.
+    Global,
+    Chat,
+    /// A persona (`roles`).
+    Role,
+}
+
+impl Scope {
+    fn table(self) -> &'static str {
+        match self {
+            Scope::Global => "global_world_infos",
+            Scope::Chat => "chat_world_infos",
+            Scope::Role => "role_world_infos",
+        }
+    }
+
+    /// The owner column, None for the global list.
+    fn owner(self) -> Option<&'static str> {
+        match self {
+            Scope::Global => None,
+            Scope::Chat => Some("chat_id"),
+            Scope::Role => Some("role_id"),
+        }
+    }
+}
+
+/// The WHERE clause selecting `owner`'s links and its parameter; nothing for
+/// the global list.
+fn owner_filter<'a>(scope: Scope, owner: &'a &'a str) -> (String, Vec<&'a dyn ToSql>) {
+    match scope.owner() {
+        Some(column) => (format!("WHERE {} = ?1", column), vec![owner]),
+        None => (String::new(), Vec::new()),
+    }
+}
+
+fn get_links(conn: &Connection, scope: Scope, owner: &str) -> Result<Vec<String>, String> {
+    let (filter, args) = owner_filter(scope, &owner);
+    let mut stmt = conn
+        .prepare(&format!("SELECT world_info_id FROM {} {} ORDER BY position", scope.table(), filter))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(args.as_slice(), |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+}
+
+/// Replaces the books linked to `owner`, keeping their order and the first of
+/// any duplicates.
+fn set_links(app: &AppHandle, scope: Scope, owner: &str, world_info_ids: &[String]) -> Result<(), String> {
+    let mut conn = get_connection(app)?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let (filter, args) = owner_filter(scope, &owner);
+    tx.execute(&format!("DELETE FROM {} {}", scope.table(), filter), args.as_slice())
+        .map_err(|e| e.to_string())?;
+
+    let columns = scope.owner().map_or(String::new(), |column| format!("{}, ", column));
+    let insert = format!(
+        "INSERT OR IGNORE INTO {} ({}world_info_id, position) VALUES ({})",
+        scope.table(),
+        columns,
+        (1..=args.len() + 2).map(|i| format!("?{}", i)).collect::<Vec<_>>().join(", ")
+    );
+    for (position, id) in world_info_ids.iter().enumerate() {
+        let position = position as i64;
+        let mut values = args.clone();
+        values.extend([id as &dyn ToSql, &position]);
+        tx.execute(&insert, values.as_slice()).map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Removes a deleted book from every chat, persona and the global list.
+pub(crate) fn unlink_world_info(conn: &Connection, world_info_id: &str) -> Result<(), String> {
+    for scope in [Scope::Global, Scope::Chat, Scope::Role] {
+        conn.execute(
+            &format!("DELETE FROM {} WHERE world_info_id = ?1", scope.table()),
+            [world_info_id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Gives a cloned chat the books of the chat it was cloned from.
+pub(crate) fn copy_chat_world_infos(conn: &Connection, from_chat_id: &str, to_chat_id: &str) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO chat_world_infos (chat_id, world_info_id, position)
+         SELECT ?2, world_info_id, position FROM chat_world_infos WHERE chat_id = ?1",
+        params![from_chat_id, to_chat_id],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Every book that applies to a chat: its character's, then the chat's own,
+/// then the persona's, then the global ones, without duplicates. Earlier books
+/// win inclusion group ties and get their budget first.
+pub(crate) fn active_world_info_ids(
+    app: &AppHandle,
+    character_ids: &[String],
+    chat_id: &str,
+    role_id: Option<&str>,
+) -> Result<Vec<String>, String> {
+    let conn = get_connection(app)?;
+    let mut ids: Vec<String> = character_ids.to_vec();
+    if !chat_id.is_empty() {
+        ids.extend(get_links(&conn, Scope::Chat, chat_id)?);
+    }
+    if let Some(role_id) = role_id {
+        ids.extend(get_links(&conn, Scope::Role, role_id)?);
+    }
+    ids.extend(get_links(&conn, Scope::Global, "")?);
+
+    let mut seen = HashSet::new();
+    ids.retain(|id| seen.insert(id.clone()));
+    Ok(ids)
+}
+
+/// Books active in every chat, in order.
+#[tauri::command]
+pub fn get_global_world_infos(app: AppHandle) -> Result<Vec<String>, String> {
+    get_links(&get_connection(&app)?, Scope::Global, "")
+}
+
+#[tauri::command]
+pub fn set_global_world_infos(app: AppHandle, world_info_ids: Vec<String>) -> Result<(), String> {
+    set_links(&app, Scope::Global, "", &world_info_ids)
+}
+
+/// Books attached to one chat on top of its character's.
+#[tauri::command]
+pub fn get_chat_world_infos(app: AppHandle, chat_id: String) -> Result<Vec<String>, String> {
+    get_links(&get_connection(&app)?, Scope::Chat, &chat_id)
+}
+
+#[tauri::command]
+pub fn set_chat_world_infos(app: AppHandle, chat_id: String, world_info_ids: Vec<String>) -> Result<(), String> {
+    set_links(&app, Scope::Chat, &chat_id, &world_info_ids)
+}
+
+/// Books active whenever the persona is.
+#[tauri::command]
+pub fn get_role_world_infos(app: AppHandle, role_id: String) -> Result<Vec<String>, String> {
+    get_links(&get_connection(&app)?, Scope::Role, &role_id)
+}
+
+#[tauri::command]
+pub fn set_role_world_infos(app: AppHandle, role_id: String, world_info_ids: Vec<String>) -> Result<(), String> {
+    set_links(&app, Scope::Role, &role_id, &world_info_ids)
+}
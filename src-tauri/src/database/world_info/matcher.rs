@@ -0,0 +1,263 @@
+use aho_corasick::AhoCorasick;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use regex::{Regex, RegexBuilder};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use super::WorldInfoEntry;
+
+/// Matchers of the saved world infos by ID, with the keys_hash of the entries
+/// they were built from. Built on first use and replaced whenever
+/// create_world_info / update_world_info saves new entries, so sending a
+/// message never has to compile a book's keys.
+static MATCHERS: Lazy<Mutex<HashMap<String, CachedMatcher>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+type CachedMatcher = (u64, Arc<KeyMatcher>);
+
+/// Everything a matcher is built from: the number of entries and their keys.
+fn keys_hash(entries: &[WorldInfoEntry]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    entries.len().hash(&mut hasher);
+    for entry in entries {
+        entry.keys.hash(&mut hasher);
+        entry.secondary_keys.hash(&mut hasher);
+        entry.case_sensitive.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// The cached matcher of a saved world info, built from `entries` on a miss.
+/// A matcher built from other entries is a miss too: a prompt can load a
+/// book that was just saved before its matcher is rebuilt.
+pub(crate) fn cached(id: &str, entries: &[WorldInfoEntry]) -> Arc<KeyMatcher> {
+    let hash = keys_hash(entries);
+    if let Some((built_from, matcher)) = MATCHERS.lock().get(id) {
+        if *built_from == hash {
+            return matcher.clone();
+        }
+    }
+    // Built outside the lock; two racing builds of the same book are harmless.
+    let matcher = Arc::new(KeyMatcher::new(entries));
+    MATCHERS.lock().insert(id.to_string(), (hash, matcher.clone()));
+    matcher
+}
+
+/// Rebuilds a world info's matcher after its entries were saved.
+pub(crate) fn rebuild(id: &str, entries: &[WorldInfoEntry]) {
+    let matcher = Arc::new(KeyMatcher::new(entries));
+    MATCHERS.lock().insert(id.to_string(), (keys_hash(entries), matcher));
+}
+
+pub(crate) fn forget(id: &str) {
+    MATCHERS.lock().remove(id);
+}
+
+/// Which automaton a plain key is in, and its pattern there.
+#[derive(Clone, Copy)]
+enum Literal {
+    /// Lowercased, matched against the lowercased text.
+    Folded(usize),
+    Exact(usize),
+}
+
+enum CompiledKey {
+    Literal(Literal),
+    Regex(Regex),
+    /// An invalid regex; never matches.
+    Invalid,
+}
+
+/// An entry's non-blank keys, compiled. Primary keys keep their text for
+/// reporting which one matched.
+struct EntryKeys {
+    primary: Vec<(String, CompiledKey)>,
+    secondary: Vec<CompiledKey>,
+}
+
+/// A pattern occurs somewhere in the text.
+const ANY: u8 = 1;
+/// ... and at least once with word boundaries where whole words need them.
+const WHOLE: u8 = 2;
+
+/// Flags per pattern of both automatons, from one pass over a text.
+pub(crate) struct Hits {
+    folded: Vec<u8>,
+    exact: Vec<u8>,
+}
+
+/// All plain keys of a book in two Aho-Corasick automatons (case-folded and
+/// exact), so a text is scanned once for the whole book instead of once per
+/// key. Regex keys are compiled once and run on their own.
+pub(crate) struct KeyMatcher {
+    folded: Patterns,
+    exact: Patterns,
+    /// Same order as the book's entries.
+    entries: Vec<EntryKeys>,
+}
+
+#[derive(Default)]
+struct Patterns {
+    automaton: Option<AhoCorasick>,
+    strings: Vec<String>,
+    ids: HashMap<String, usize>,
+}
+
+impl Patterns {
+    fn add(&mut self, pattern: String) -> usize {
+        if let Some(&id) = self.ids.get(&pattern) {
+            return id;
+        }
+        let id = self.strings.len();
+        self.ids.insert(pattern.clone(), id);
+        self.strings.push(pattern);
+        id
+    }
+
+    fn build(&mut self) {
+        if self.strings.is_empty() {
+            return;
+        }
+        match AhoCorasick::new(&self.strings) {
+            Ok(automaton) => self.automaton = Some(automaton),
+            // Only fails past size limits no lorebook gets near.
+            Err(e) => eprintln!("[WorldInfo] Failed to build key matcher: {}", e),
+        }
+    }
+
+    fn scan(&self, haystack: &str) -> Vec<u8> {
+        let mut flags = vec![0; self.strings.len()];
+        let Some(automaton) = &self.automaton else {
+            return flags;
+        };
+        for m in automaton.find_overlapping_iter(haystack) {
+            let id = m.pattern().as_usize();
+            flags[id] |= ANY;
+            if flags[id] & WHOLE == 0 && at_word_boundaries(haystack, &self.strings[id], m.start(), m.end()) {
+                flags[id] |= WHOLE;
+            }
+        }
+        flags
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Whether `key` at start..end of `haystack` stands as a whole word. A
+/// boundary is only required on a side where the key itself starts or ends
+/// with a word character, so keys like "Dr." still match.
+fn at_word_boundaries(haystack: &str, key: &str, start: usize, end: usize) -> bool {
+    let check_start = key.chars().next().is_some_and(is_word_char);
+    let check_end = key.chars().next_back().is_some_and(is_word_char);
+    (!check_start || !haystack[..start].chars().next_back().is_some_and(is_word_char))
+        && (!check_end || !haystack[end..].chars().next().is_some_and(is_word_char))
+}
+
+/// Keys written as /pattern/flags are regexes (the same notation lorebooks
+/// from other frontends use). None for plain keys.
+fn parse_regex_key(key: &str) -> Option<Result<Regex, regex::Error>> {
+    let rest = key.strip_prefix('/')?;
+    let end = rest.rfind('/')?;
+    let (pattern, flags) = (&rest[..end], &rest[end + 1..]);
+    if pattern.is_empty() || !flags.chars().all(|c| "gimsuy".contains(c)) {
+        return None;
+    }
+    Some(
+        RegexBuilder::new(pattern)
+            .case_insensitive(flags.contains('i'))
+            .multi_line(flags.contains('m'))
+            .dot_matches_new_line(flags.contains('s'))
+            .build(),
+    )
+}
+
+fn non_blank(keys: &[String]) -> impl Iterator<Item = &str> {
+    keys.iter().map(|k| k.trim()).filter(|k| !k.is_empty())
+}
+
+impl KeyMatcher {
+    pub(crate) fn new(entries: &[WorldInfoEntry]) -> Self {
+        let mut folded = Patterns::default();
+        let mut exact = Patterns::default();
+        let mut compile = |key: &str, entry: &WorldInfoEntry| match parse_regex_key(key) {
+            Some(Ok(re)) => CompiledKey::Regex(re),
+            Some(Err(e)) => {
+                eprintln!("[WorldInfo] Invalid regex key {}: {}", key, e);
+                CompiledKey::Invalid
+            }
+            None if entry.case_sensitive => CompiledKey::Literal(Literal::Exact(exact.add(key.to_string()))),
+            None => CompiledKey::Literal(Literal::Folded(folded.add(key.to_lowercase()))),
+        };
+
+        let entries = entries
+            .iter()
+            .map(|entry| EntryKeys {
+                primary: non_blank(&entry.keys)
+                    .map(|k| (k.to_string(), compile(k, entry)))
+                    .collect(),
+                secondary: non_blank(&entry.secondary_keys).map(|k| compile(k, entry)).collect(),
+            })
+            .collect();
+
+        folded.build();
+        exact.build();
+        KeyMatcher { folded, exact, entries }
+    }
+
+    /// Runs both automatons over a text and its lowercased copy.
+    pub(crate) fn scan(&self, (text, lower): &(String, String)) -> Hits {
+        Hits {
+            folded: self.folded.scan(lower),
+            exact: self.exact.scan(text),
+        }
+    }
+
+    fn key_matches(&self, key: &CompiledKey, whole_words: bool, text: &str, hits: &Hits) -> bool {
+        let flags = match key {
+            CompiledKey::Regex(re) => return re.is_match(text),
+            CompiledKey::Invalid => return false,
+            CompiledKey::Literal(Literal::Folded(id)) => hits.folded[*id],
+            CompiledKey::Literal(Literal::Exact(id)) => hits.exact[*id],
+        };
+        flags & if whole_words { WHOLE } else { ANY } != 0
+    }
+
+    /// The first primary key of entry `index` found in `text`, None if none is
+    /// (or a key-less entry, see has_keys). `hits` must come from scan() of
+    /// the same text. An index past the entries the matcher was built from
+    /// matches nothing.
+    pub(crate) fn primary_match(&self, index: usize, entry: &WorldInfoEntry, text: &str, hits: &Hits) -> Option<&str> {
+        self.entries
+            .get(index)?
+            .primary
+            .iter()
+            .find(|(_, key)| self.key_matches(key, entry.match_whole_words, text, hits))
+            .map(|(key, _)| key.as_str())
+    }
+
+    /// None for an index past the entries the matcher was built from.
+    pub(crate) fn has_keys(&self, index: usize) -> Option<bool> {
+        self.entries.get(index).map(|keys| !keys.primary.is_empty())
+    }
+
+    /// Whether each secondary key of entry `index` is found, in order.
+    pub(crate) fn secondary_matches<'m>(
+        &'m self,
+        index: usize,
+        entry: &WorldInfoEntry,
+        text: &'m str,
+        hits: &'m Hits,
+    ) -> impl Iterator<Item = bool> + 'm {
+        let whole_words = entry.match_whole_words;
+        self.entries
+            .get(index)
+            .into_iter()
+            .flat_map(|keys| &keys.secondary)
+            .map(move |key| self.key_matches(key, whole_words, text, hits))
+    }
+}
@@ -0,0 +1,290 @@
+use tauri::AppHandle;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::database::get_connection;
+
+pub(crate) mod activation;
+pub mod links;
+pub(crate) mod matcher;
+pub(crate) mod timed_effects;
+
+/// How an entry's secondary keys combine with its primary ones. The primary
+/// keys always have to match; this decides what the secondary keys add.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectiveLogic {
+    /// At least one secondary key matches.
+    #[default]
+    AndAny,
+    /// Every secondary key matches.
+    AndAll,
+    /// No secondary key matches.
+    NotAny,
+    /// At least one secondary key doesn't match.
+    NotAll,
+}
+
+/// Who an entry placed at a depth speaks as. Ordered the way injections
+/// sharing a depth are placed.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum InjectionRole {
+    #[default]
+    System,
+    User,
+    Assistant,
+}
+
+impl InjectionRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            InjectionRole::System => "system",
+            InjectionRole::User => "user",
+            InjectionRole::Assistant => "assistant",
+        }
+    }
+}
+
+fn default_probability() -> u32 {
+    100
+}
+
+fn default_group_weight() -> u32 {
+    100
+}
+
+/// Fields added after the first version default, so entries saved before
+/// them keep loading.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WorldInfoEntry {
+    pub id:       String,
+    /// Plain text, or a regex written as /pattern/flags. No keys = always active.
+    pub keys:     Vec<String>,
+    pub content:  String,
+    pub enabled:  bool,
+    pub comment:  String,
+    /// "before" / "after" the other context attached to the last user turn,
+    /// or "at_depth" to be inserted into the history; see depth and role.
+    pub position: String,
+    /// Only checked when non-empty; see SelectiveLogic.
+    #[serde(default)]
+    pub secondary_keys: Vec<String>,
+    #[serde(default)]
+    pub selective_logic: SelectiveLogic,
+    /// Plain keys match case-insensitively unless set. Regex keys use their flags.
+    #[serde(default)]
+    pub case_sensitive: bool,
+    /// Plain keys only match as whole words, e.g. "cat" doesn't match "concatenate".
+    #[serde(default)]
+    pub match_whole_words: bool,
+    /// Overrides the lorebook's scan depth for this entry.
+    #[serde(default)]
+    pub scan_depth: Option<u32>,
+    /// Only the chat can trigger this entry, not other entries' content.
+    #[serde(default)]
+    pub exclude_recursion: bool,
+    /// This entry's content isn't scanned for further keys.
+    #[serde(default)]
+    pub prevent_recursion: bool,
+    /// Once triggered, stays active for this many more messages without its keys.
+    #[serde(default)]
+    pub sticky: u32,
+    /// After triggering (and after sticky runs out), can't trigger again for
+    /// this many messages.
+    #[serde(default)]
+    pub cooldown: u32,
+    /// Can't trigger before the chat has this many messages.
+    #[serde(default)]
+    pub delay: u32,
+    /// Active without its keys.
+    #[serde(default)]
+    pub constant: bool,
+    /// Chance in percent that the entry is inserted once triggered.
+    #[serde(default = "default_probability")]
+    pub probability: u32,
+    /// Activated entries are inserted from lowest to highest order.
+    #[serde(default)]
+    pub order: i32,
+    /// Inclusion group. Of the entries of a group that trigger, only the one
+    /// with the highest `group_weight` is inserted. Empty = no group.
+    #[serde(default)]
+    pub group: String,
+    #[serde(default = "default_group_weight")]
+    pub group_weight: u32,
+    /// For "at_depth": messages from the end of the chat; 0 = after the last one.
+    #[serde(default)]
+    pub depth: u32,
+    /// For "at_depth": the role of the inserted message.
+    #[serde(default)]
+    pub role: InjectionRole,
+}
+
+#[derive(Serialize)]
+pub struct DbWorldInfo {
+    pub id:          String,
+    pub name:        String,
+    pub description: String,
+    pub entries:     Vec<WorldInfoEntry>,
+    /// How many of the latest messages keys are matched against;
+    /// None = activation::DEFAULT_SCAN_DEPTH.
+    pub scan_depth:  Option<u32>,
+    /// Upper bound for the tokens this book's activated entries may add; None = unlimited.
+    pub token_budget: Option<u32>,
+    /// Also activates entries whose content is at least this similar (cosine,
+    /// 0..1) to the recent messages; None = keywords only.
+    pub semantic_threshold: Option<f32>,
+    pub created_at:  String,
+}
+
+#[derive(Deserialize)]
+pub struct WorldInfoPayload {
+    pub name:        String,
+    pub description: Option<String>,
+    pub entries:     Vec<WorldInfoEntry>,
+    #[serde(default)]
+    pub scan_depth:  Option<u32>,
+    #[serde(default)]
+    pub token_budget: Option<u32>,
+    #[serde(default)]
+    pub semantic_threshold: Option<f32>,
+}
+
+#[tauri::command]
+pub fn get_world_infos(app: AppHandle) -> Result<Vec<DbWorldInfo>, String> {
+    let conn = get_connection(&app)?;
+    let mut stmt = conn.prepare(
+        "SELECT id, name, description, entries, created_at, scan_depth, token_budget, semantic_threshold
+         FROM world_infos ORDER BY created_at DESC",
+    ).map_err(|e| e.to_string())?;
+
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+            row.get::<_, Option<u32>>(5)?,
+            row.get::<_, Option<u32>>(6)?,
+            row.get::<_, Option<f32>>(7)?,
+        ))
+    }).map_err(|e| e.to_string())?;
+
+    let mut list = Vec::new();
+    for row in rows {
+        let (id, name, description, entries_json, created_at, scan_depth, token_budget, semantic_threshold) =
+            row.map_err(|e| e.to_string())?;
+        let entries: Vec<WorldInfoEntry> =
+            serde_json::from_str(&entries_json).unwrap_or_default();
+        list.push(DbWorldInfo {
+            id,
+            name,
+            description,
+            entries,
+            scan_depth,
+            token_budget,
+            semantic_threshold,
+            created_at,
+        });
+    }
+    Ok(list)
+}
+
+#[tauri::command]
+pub fn create_world_info(app: AppHandle, payload: WorldInfoPayload) -> Result<String, String> {
+    let conn = get_connection(&app)?;
+    let new_id = Uuid::new_v4().to_string();
+    let entries_json = serde_json::to_string(&payload.entries)
+        .map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "INSERT INTO world_infos (id, name, description, entries, scan_depth, token_budget, semantic_threshold)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            new_id,
+            payload.name,
+            payload.description.unwrap_or_default(),
+            entries_json,
+            payload.scan_depth,
+            payload.token_budget,
+            payload.semantic_threshold,
+        ],
+    ).map_err(|e| e.to_string())?;
+
+    matcher::rebuild(&new_id, &payload.entries);
+    Ok(new_id)
+}
+
+#[tauri::command]
+pub fn update_world_info(app: AppHandle, id: String, payload: WorldInfoPayload) -> Result<(), String> {
+    let conn = get_connection(&app)?;
+    let entries_json = serde_json::to_string(&payload.entries)
+        .map_err(|e| e.to_string())?;
+    // Renaming a book or changing its settings keeps the matcher.
+    let entries_changed = conn.query_row(
+        "SELECT entries FROM world_infos WHERE id = ?1",
+        params![id],
+        |row| row.get::<_, String>(0),
+    ).map_or(true, |stored| stored != entries_json);
+
+    conn.execute(
+        "UPDATE world_infos
+         SET name = ?1, description = ?2, entries = ?3, scan_depth = ?4, token_budget = ?5,
+             semantic_threshold = ?6
+         WHERE id = ?7",
+        params![
+            payload.name,
+            payload.description.unwrap_or_default(),
+            entries_json,
+            payload.scan_depth,
+            payload.token_budget,
+            payload.semantic_threshold,
+            id,
+        ],
+    ).map_err(|e| e.to_string())?;
+
+    if entries_changed {
+        matcher::rebuild(&id, &payload.entries);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn delete_world_info(app: AppHandle, id: String) -> Result<(), String> {
+    let conn = get_connection(&app)?;
+
+    let mut stmt = conn
+        .prepare("SELECT id, world_info_ids FROM characters WHERE world_info_ids LIKE ?1")
+        .map_err(|e| e.to_string())?;
+
+    let pattern = format!("%{}%", id);
+
+    let rows: Vec<(String, String)> = stmt
+        .query_map([&pattern], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    for (char_id, world_info_ids_json) in rows {
+        let mut ids: Vec<String> = serde_json::from_str(&world_info_ids_json)
+            .unwrap_or_default();
+
+        ids.retain(|wid| wid != &id);
+
+        let updated = serde_json::to_string(&ids).map_err(|e| e.to_string())?;
+
+        conn.execute(
+            "UPDATE characters SET world_info_ids = ?1 WHERE id = ?2",
+            params![updated, char_id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    links::unlink_world_info(&conn, &id)?;
+    conn.execute("DELETE FROM world_infos WHERE id = ?1", params![id])
+        .map_err(|e| e.to_string())?;
+
+    matcher::forget(&id);
+    Ok(())
+}
\ No newline at end of file
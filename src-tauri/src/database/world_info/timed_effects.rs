@@ -0,0 +1,112 @@
+use rusqlite::{params, Connection};
+use tauri::AppHandle;
+
+use super::activation::{Started, Timeline};
+use crate::database::get_connection;
+
+/// Sticky and cooldown windows of a chat's entries as of `position` (the number
+/// of messages before the one being generated). Effects started at or after
+/// `position` belong to generations that are being redone and are ignored.
+pub(crate) fn load_timeline(app: &AppHandle, chat_id: &str, position: u32) -> Result<Timeline, String> {
+    let conn = get_connection(app)?;
+    let mut stmt = conn.prepare(
+        "SELECT book_id, entry_id, sticky_until, cooldown_until FROM world_info_timed_effects
+         WHERE chat_id = ?1 AND activated_at < ?2",
+    ).map_err(|e| e.to_string())?;
+
+    let rows = stmt.query_map(params![chat_id, position], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, u32>(2)?,
+            row.get::<_, u32>(3)?,
+        ))
+    }).map_err(|e| e.to_string())?;
+
+    let mut timeline = Timeline { position, ..Default::default() };
+    for row in rows {
+        let (book_id, entry_id, sticky_until, cooldown_until) = row.map_err(|e| e.to_string())?;
+        let set = if position < sticky_until {
+            &mut timeline.sticky
+        } else if position < cooldown_until {
+            &mut timeline.cooldown
+        } else {
+            continue;
+        };
+        set.entry(book_id).or_default().insert(entry_id);
+    }
+    Ok(timeline)
+}
+
+/// The sticky / cooldown windows that a prompt's world info starts, held
+/// until the reply it was built for is kept; see record_started.
+#[derive(Clone, Default)]
+pub(crate) struct StartedEffects {
+    chat_id: String,
+    position: u32,
+    /// Book ID, entry ID, sticky and cooldown length.
+    entries: Vec<(String, String, u32, u32)>,
+}
+
+impl StartedEffects {
+    pub(crate) fn new(chat_id: &str, timeline: &Timeline, started: &[Started]) -> Self {
+        StartedEffects {
+            chat_id: chat_id.to_string(),
+            position: timeline.position,
+            entries: started
+                .iter()
+                .map(|s| (s.book_id.to_string(), s.entry.id.clone(), s.entry.sticky, s.entry.cooldown))
+                .collect(),
+        }
+    }
+}
+
+/// Stores the effects that start with the message at `effects.position`,
+/// replacing whatever an earlier attempt at that message (or later ones)
+/// left behind. Windows start with the next message: sticky first, then
+/// the cooldown. Does nothing for a prompt without a chat.
+pub(crate) fn record_started(app: &AppHandle, effects: &StartedEffects) -> Result<(), String> {
+    if effects.chat_id.is_empty() {
+        return Ok(());
+    }
+    let mut conn = get_connection(app)?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let (chat_id, position) = (&effects.chat_id, effects.position);
+
+    tx.execute(
+        "DELETE FROM world_info_timed_effects WHERE chat_id = ?1 AND activated_at >= ?2",
+        params![chat_id, position],
+    ).map_err(|e| e.to_string())?;
+
+    for (book_id, entry_id, sticky, cooldown) in &effects.entries {
+        let sticky_until = position + 1 + sticky;
+        let cooldown_until = sticky_until + cooldown;
+        tx.execute(
+            "INSERT OR REPLACE INTO world_info_timed_effects
+                (chat_id, book_id, entry_id, activated_at, sticky_until, cooldown_until)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![chat_id, book_id, entry_id, position, sticky_until, cooldown_until],
+        ).map_err(|e| e.to_string())?;
+    }
+
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Copies the effects started up to and including message `through_position`
+/// into a cloned chat, so the branch continues from the same state.
+pub(crate) fn copy_timed_effects(
+    conn: &Connection,
+    from_chat_id: &str,
+    to_chat_id: &str,
+    through_position: u32,
+) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO world_info_timed_effects
+            (chat_id, book_id, entry_id, activated_at, sticky_until, cooldown_until)
+         SELECT ?2, book_id, entry_id, activated_at, sticky_until, cooldown_until
+         FROM world_info_timed_effects
+         WHERE chat_id = ?1 AND activated_at <= ?3",
+        params![from_chat_id, to_chat_id, through_position],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -2,8 +2,13 @@ mod ai;
 mod database;
 mod import;
 mod export;
+mod prompt;
 mod tokenizer;
 
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench;
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
@@ -13,8 +18,16 @@ pub fn run() {
         })
         .invoke_handler(tauri::generate_handler![
             ai::call_ai_api,
+            ai::background::generate_into_chat,
+            ai::swipes::generate_swipes,
+            ai::continuation::continue_generation,
+            ai::impersonate::impersonate,
+            ai::character_memory::extract_character_memories,
             ai::fetch_models,
             ai::stop_generation,
+            ai::list_active_generations,
+            prompt::build_prompt,
+            prompt::test_bench::test_world_info,
             database::chats::get_conversations,
             database::chats::create_chat,
             database::chats::delete_chat,
@@ -52,6 +65,19 @@ pub fn run() {
             database::world_info::create_world_info,
             database::world_info::update_world_info,
             database::world_info::delete_world_info,
+            database::world_info::links::get_global_world_infos,
+            database::world_info::links::set_global_world_infos,
+            database::world_info::links::get_chat_world_infos,
+            database::world_info::links::set_chat_world_infos,
+            database::world_info::links::get_role_world_infos,
+            database::world_info::links::set_role_world_infos,
+            database::generation_requests::get_generation_request,
+            database::memory::get_memory_chunks,
+            database::memory::clear_memory,
+            database::character_memories::get_character_memories,
+            database::character_memories::create_character_memory,
+            database::character_memories::update_character_memory,
+            database::character_memories::delete_character_memory,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,156 @@
+use tauri::AppHandle;
+
+use super::text::strip_thinking;
+use super::{
+    language, load_character, load_history, load_role, PromptOptions, DEFAULT_CHAR_NAME,
+    DEFAULT_USER_NAME,
+};
+use crate::database::{character_memories, chats, settings};
+use crate::tokenizer::count_tokens;
+
+/// Most notes one extraction may add; the rest of a long chat is usually
+/// detail the character doesn't need weeks later.
+const MAX_NEW_NOTES: usize = 12;
+
+/// Whether character memory goes into prompts: the option, else the
+/// "character_memory" setting, else off.
+fn enabled(app: &AppHandle, options: &PromptOptions) -> Result<bool, String> {
+    if let Some(enabled) = options.character_memory {
+        return Ok(enabled);
+    }
+    Ok(settings::get_setting(app, "character_memory")?.is_some_and(|v| v.trim() == "true"))
+}
+
+/// The notes the chat's character keeps about the user's persona, one per
+/// line; empty if character memory is off or there are none.
+pub(super) fn memory_notes(app: &AppHandle, chat_id: &str, options: &PromptOptions) -> Result<String, String> {
+    if !enabled(app, options)? {
+        return Ok(String::new());
+    }
+    let Some(character_id) = chats::get_chat_character_id(app, chat_id)? else {
+        return Ok(String::new());
+    };
+    let notes = character_memories::active_memories(app, &character_id, options.role_id.as_deref())?;
+    Ok(bullets(&notes))
+}
+
+fn bullets(notes: &[String]) -> String {
+    notes.iter().map(|n| format!("- {}", n)).collect::<Vec<_>>().join("\n")
+}
+
+/// An extraction request for one chat.
+pub(crate) struct ExtractionPrompt {
+    pub character_id: String,
+    /// Single user message: the instruction, then the chat.
+    pub prompt: String,
+    /// Notes the character already has, to drop repeats from the reply.
+    pub known: Vec<String>,
+}
+
+/// Builds the request that asks the model what the chat's character should
+/// remember from it: the rolling summary and the messages after it, and the
+/// notes the character already has for the persona in `options`.
+/// The prompt stays within `token_budget` (counted with the options' model):
+/// the oldest messages are left out until it fits.
+pub(crate) fn extraction_prompt(
+    app: &AppHandle,
+    chat_id: &str,
+    options: &PromptOptions,
+    token_budget: u32,
+) -> Result<ExtractionPrompt, String> {
+    let character_id = chats::get_chat_character_id(app, chat_id)?
+        .ok_or_else(|| format!("Chat {} has no character", chat_id))?;
+    let character = load_character(app, chat_id, options)?;
+    let role = load_role(app, options)?;
+    let char_name = if character.name.is_empty() { DEFAULT_CHAR_NAME } else { &character.name };
+    let user_name = if role.name.is_empty() { DEFAULT_USER_NAME } else { &role.name };
+
+    let summary = chats::get_summary_meta(app.clone(), chat_id.to_string())?;
+    let history = load_history(app, chat_id, options)?;
+    let from = summary
+        .last_id
+        .and_then(|last| history.iter().position(|m| m.id == last))
+        .map_or(0, |i| i + 1);
+    let transcript: Vec<String> = history[from..]
+        .iter()
+        .map(|m| {
+            let speaker = if m.role == "user" { user_name } else { char_name };
+            format!("{}: {}", speaker, m.content.trim())
+        })
+        .collect();
+    if transcript.is_empty() && summary.summary.as_deref().unwrap_or_default().is_empty() {
+        return Err("Nothing to remember: the chat is empty.".to_string());
+    }
+
+    let known = character_memories::active_memories(app, &character_id, options.role_id.as_deref())?;
+    let mut prompt = format!(
+        "You maintain the long-term memory of {char}, a roleplay character. Read the finished \
+         session below and list what {char} should still remember about {user} the next time \
+         they meet, in a new conversation.\n\
+         Rules: \
+         (1) Only lasting things: who {user} is, what they told {char} about themselves, \
+         promises, shared history, how their relationship stands. \
+         (2) Skip scene details, small talk and anything {char} already remembers. \
+         (3) One short, self-contained sentence per line, starting with \"- \", written from \
+         an outside view using their names. \
+         (4) At most {max} lines. Write in {lang}. \
+         (5) If there is nothing new worth remembering, answer only NONE.\n\
+         Output only the list — no intro, no labels, no commentary.",
+        char = char_name,
+        user = user_name,
+        max = MAX_NEW_NOTES,
+        lang = language(app, options)?,
+    );
+    if !known.is_empty() {
+        let heading = format!("WHAT {} ALREADY REMEMBERS", char_name.to_uppercase());
+        prompt.push_str(&format!("\n\n{}:\n{}", heading, bullets(&known)));
+    }
+    let summary = summary.summary.filter(|s| !s.trim().is_empty());
+    if let Some(summary) = &summary {
+        prompt.push_str(&format!("\n\nSUMMARY OF THE SESSION SO FAR:\n{}", summary.trim()));
+    }
+    let transcript = fit_transcript(&transcript, &prompt, token_budget, options);
+    if !transcript.is_empty() {
+        prompt.push_str(&format!("\n\nSESSION:\n{}", transcript));
+    } else if summary.is_none() {
+        return Err("Not even the last message fits into the context window.".to_string());
+    }
+
+    Ok(ExtractionPrompt { character_id, prompt, known })
+}
+
+/// The newest lines of `transcript` that fit into what `token_budget` leaves
+/// after `prompt`, joined like the rest of the prompt.
+fn fit_transcript(transcript: &[String], prompt: &str, token_budget: u32, options: &PromptOptions) -> String {
+    let model = options.model.clone().unwrap_or_default();
+    let mut left = token_budget.saturating_sub(count_tokens(format!("{}\n\nSESSION:\n", prompt), model.clone()));
+    let mut from = transcript.len();
+    while from > 0 {
+        // +1 for the blank line between two messages.
+        let tokens = count_tokens(transcript[from - 1].clone(), model.clone()) + 1;
+        if tokens > left {
+            break;
+        }
+        left -= tokens;
+        from -= 1;
+    }
+    transcript[from..].join("\n\n")
+}
+
+/// The notes in an extraction reply: its "- " / "* " lines outside thinking
+/// blocks, without ones the character already has. Empty for NONE.
+pub(crate) fn parse_notes(reply: &str, known: &[String]) -> Vec<String> {
+    let mut notes: Vec<String> = Vec::new();
+    for line in strip_thinking(reply).lines() {
+        let Some(note) = line.trim().strip_prefix("- ").or_else(|| line.trim().strip_prefix("* ")) else {
+            continue;
+        };
+        let note = note.trim();
+        let seen = |n: &String| n.eq_ignore_ascii_case(note);
+        if !note.is_empty() && !known.iter().any(seen) && !notes.iter().any(seen) {
+            notes.push(note.to_string());
+        }
+    }
+    notes.truncate(MAX_NEW_NOTES);
+    notes
+}
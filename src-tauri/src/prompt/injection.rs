@@ -0,0 +1,72 @@
+use std::cmp::Reverse;
+
+use serde::Deserialize;
+
+use super::PromptMessage;
+use crate::database::world_info::InjectionRole;
+
+/// Text the caller wants placed in the history, e.g. an author's note.
+#[derive(Deserialize, Clone)]
+pub struct PromptInjection {
+    pub content: String,
+    /// Messages from the end of the chat; 0 = after the last one.
+    #[serde(default)]
+    pub depth: u32,
+    #[serde(default)]
+    pub role: InjectionRole,
+    /// Same as a world info entry's order, for injections sharing a depth and role.
+    #[serde(default)]
+    pub order: i32,
+}
+
+/// One piece of text to insert, from a world info entry or a PromptInjection.
+pub(crate) struct Injection {
+    pub depth: u32,
+    pub role: InjectionRole,
+    pub order: i32,
+    pub content: String,
+}
+
+/// Inserts `injections` into `prompt`, counting depth from its end and never
+/// going above the leading system message. Injections with the same depth and
+/// role become one message; at the same spot, deeper ones come first, then
+/// system, user, assistant. Within a message they're sorted by order and
+/// otherwise keep the order they were given in, so the result is stable.
+pub(crate) fn inject(prompt: Vec<PromptMessage>, mut injections: Vec<Injection>) -> Vec<PromptMessage> {
+    if injections.is_empty() {
+        return prompt;
+    }
+    injections.sort_by_key(|i| (Reverse(i.depth), i.role, i.order));
+
+    let mut merged: Vec<(usize, PromptMessage)> = Vec::new();
+    let top = usize::from(prompt.first().is_some_and(|m| m.role == "system"));
+    let mut current: Option<(u32, InjectionRole)> = None;
+    for injection in injections {
+        let content = injection.content.trim();
+        if content.is_empty() {
+            continue;
+        }
+        match merged.last_mut() {
+            Some((_, message)) if current == Some((injection.depth, injection.role)) => {
+                message.content.push_str("\n\n");
+                message.content.push_str(content);
+            }
+            _ => {
+                let at = prompt.len().saturating_sub(injection.depth as usize).max(top);
+                merged.push((at, PromptMessage::new(injection.role.as_str(), content)));
+                current = Some((injection.depth, injection.role));
+            }
+        }
+    }
+
+    let mut merged = merged.into_iter().peekable();
+    let mut result = Vec::with_capacity(prompt.len() + merged.len());
+    for (index, message) in prompt.into_iter().enumerate() {
+        while let Some((_, injected)) = merged.next_if(|(at, _)| *at == index) {
+            result.push(injected);
+        }
+        result.push(message);
+    }
+    result.extend(merged.map(|(_, injected)| injected));
+    result
+}
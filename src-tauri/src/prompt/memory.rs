@@ -0,0 +1,160 @@
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use parking_lot::Mutex;
+use tauri::AppHandle;
+
+use super::semantic::{config, content_vectors};
+use super::HistoryMessage;
+use crate::ai::embeddings::{cosine_similarity, embed};
+use crate::database::embeddings::content_hash;
+use crate::database::memory::{self, MemoryChunk};
+use crate::tokenizer::count_tokens;
+
+/// Messages per memory chunk: about one exchange and its follow-up.
+const CHUNK_MESSAGES: usize = 4;
+/// Latest messages (the pending one included) that chunks are compared to.
+const QUERY_MESSAGES: usize = 3;
+
+/// The last recall failure that was logged.
+static LAST_ERROR: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+
+/// The last query vector per chat, with the hash of the query and the model
+/// it was embedded with. Queries change every turn, so they aren't worth a row
+/// in the embeddings table; a retry or swipe asks with the same messages.
+static QUERY_VECTORS: Lazy<Mutex<HashMap<String, QueryVector>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+type QueryVector = (String, Vec<f32>);
+
+/// How much of the summarized past to bring back into a prompt.
+pub(super) struct RecallLimits {
+    /// Chunks to consider, most similar first; 0 = memory off.
+    pub top_k: u32,
+    /// Tokens the recalled chunks may take together.
+    pub token_budget: u32,
+    /// Tokenizer for the budget.
+    pub model: String,
+}
+
+/// The summarized messages (`history[..summarized]`) in chunks of
+/// CHUNK_MESSAGES, a trailing partial chunk left for later. Lines are
+/// prefixed with the speaker's name.
+pub(super) fn chunks(
+    history: &[HistoryMessage],
+    summarized: usize,
+    speaker: &dyn Fn(&str) -> String,
+) -> Vec<MemoryChunk> {
+    history[..summarized]
+        .chunks_exact(CHUNK_MESSAGES)
+        .enumerate()
+        .map(|(position, messages)| MemoryChunk {
+            position: position as u32,
+            first_message_id: messages[0].id.clone(),
+            last_message_id: messages[CHUNK_MESSAGES - 1].id.clone(),
+            content: messages
+                .iter()
+                .map(|m| format!("{}: {}", speaker(&m.role), m.content.trim()))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        })
+        .collect()
+}
+
+async fn retrieve(
+    app: &AppHandle,
+    chat_id: &str,
+    current: Vec<MemoryChunk>,
+    scan_messages: &[&str],
+    limits: &RecallLimits,
+) -> Result<Vec<String>, String> {
+    let Some(config) = config(app)? else {
+        return Ok(Vec::new());
+    };
+
+    // Stores chunks that are new or whose messages changed since.
+    let count = current.len() as u32;
+    let stored = memory::get_chunk_hashes(app, chat_id, &config.model)?;
+    let changed: Vec<(MemoryChunk, String)> = current
+        .into_iter()
+        .map(|chunk| {
+            let hash = content_hash(&chunk.content);
+            (chunk, hash)
+        })
+        .filter(|(chunk, hash)| stored.get(&chunk.position) != Some(hash))
+        .collect();
+    if !changed.is_empty() {
+        let contents: Vec<&str> = changed.iter().map(|(chunk, _)| chunk.content.as_str()).collect();
+        let vectors = content_vectors(app, &config, &contents).await?;
+        let rows: Vec<_> = changed
+            .into_iter()
+            .filter_map(|(chunk, hash)| {
+                let vector = vectors.get(&hash)?.as_slice();
+                Some((chunk, hash, vector))
+            })
+            .collect();
+        memory::store_chunks(app, chat_id, &config.model, &rows)?;
+    }
+
+    let query = scan_messages[scan_messages.len().saturating_sub(QUERY_MESSAGES)..].join("\n");
+    let key = content_hash(&format!("{}\n{}", config.model, query));
+    let cached = match QUERY_VECTORS.lock().get(chat_id) {
+        Some((hash, vector)) if *hash == key => Some(vector.clone()),
+        _ => None,
+    };
+    let query = match cached {
+        Some(vector) => vector,
+        None => {
+            let vector = embed(&config, &[query.as_str()]).await?.pop().unwrap_or_default();
+            QUERY_VECTORS.lock().insert(chat_id.to_string(), (key, vector.clone()));
+            vector
+        }
+    };
+
+    let mut scored: Vec<(f32, MemoryChunk)> = memory::get_chunk_vectors(app, chat_id, &config.model, count)?
+        .into_iter()
+        .map(|(chunk, vector)| (cosine_similarity(&query, &vector), chunk))
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut used = 0;
+    let mut recalled = Vec::new();
+    for (_, chunk) in scored.into_iter().take(limits.top_k as usize) {
+        let tokens = count_tokens(chunk.content.clone(), limits.model.clone());
+        if used + tokens > limits.token_budget {
+            continue;
+        }
+        used += tokens;
+        recalled.push(chunk);
+    }
+    recalled.sort_by_key(|chunk| chunk.position);
+    Ok(recalled.into_iter().map(|chunk| chunk.content).collect())
+}
+
+/// Of a chat's `chunks` (see chunks()), the ones most like the latest turn,
+/// oldest first, within the limits. Chunks are embedded and saved on the way,
+/// so each one is embedded once. Recall is an extra: without an embedding
+/// endpoint, or if it fails, there's nothing.
+pub(super) async fn recall(
+    app: &AppHandle,
+    chat_id: &str,
+    chunks: Vec<MemoryChunk>,
+    scan_messages: &[&str],
+    limits: &RecallLimits,
+) -> Vec<String> {
+    if limits.top_k == 0 || chunks.is_empty() || scan_messages.is_empty() {
+        return Vec::new();
+    }
+    match retrieve(app, chat_id, chunks, scan_messages, limits).await {
+        Ok(recalled) => {
+            LAST_ERROR.lock().take();
+            recalled
+        }
+        Err(e) => {
+            // Logged once per distinct error, not on every send while the
+            // endpoint is down.
+            if LAST_ERROR.lock().replace(e.clone()).as_ref() != Some(&e) {
+                eprintln!("[Memory] Recall skipped: {}", e);
+            }
+            Vec::new()
+        }
+    }
+}
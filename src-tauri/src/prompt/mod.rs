@@ -0,0 +1,650 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tauri::AppHandle;
+
+use crate::database::world_info::activation::{self, Activation, Lorebook, Timeline};
+use crate::database::world_info::links;
+use crate::database::world_info::matcher::{self, KeyMatcher};
+use crate::database::world_info::timed_effects::{self, StartedEffects};
+use crate::database::world_info::{DbWorldInfo, WorldInfoEntry};
+use crate::database::{characters, chats, messages, roles, settings};
+use crate::tokenizer::count_tokens;
+
+pub(crate) mod character_memory;
+mod injection;
+mod memory;
+mod semantic;
+pub mod test_bench;
+mod text;
+
+use injection::{inject, Injection};
+pub use injection::PromptInjection;
+use memory::RecallLimits;
+use text::{replace_placeholders, section_label, strip_thinking};
+
+// Inserted as the first user turn when the chat opens with the character's
+// greeting; some chat templates (e.g. Qwen via LM Studio) require the first
+// non-system turn to be a user message.
+const START_ROLEPLAY_MARKER: &str = "[Start Roleplay]";
+const DEFAULT_LANGUAGE: &str = "English";
+const DEFAULT_CHAR_NAME: &str = "Unknown";
+const DEFAULT_USER_NAME: &str = "User";
+// Tokens of recalled memory chunks unless the options or settings say otherwise.
+const DEFAULT_MEMORY_BUDGET: u32 = 600;
+
+/// Character card fields the prompt uses. Sent by the frontend only for
+/// characters that don't live in the database (the built-in ones).
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct PromptCharacter {
+    pub name: String,
+    pub desc: String,
+    pub personality: String,
+    pub scenario: String,
+    #[serde(alias = "mesExample")]
+    pub mes_example: String,
+    #[serde(alias = "worldInfoIds")]
+    pub world_info_ids: Vec<String>,
+}
+
+impl From<characters::DbCharacter> for PromptCharacter {
+    fn from(c: characters::DbCharacter) -> Self {
+        PromptCharacter {
+            name: c.name,
+            desc: c.desc,
+            personality: c.personality,
+            scenario: c.scenario,
+            mes_example: c.mes_example,
+            world_info_ids: c.world_info_ids,
+        }
+    }
+}
+
+/// The user's role (persona). Like PromptCharacter, only sent for roles that
+/// aren't in the database.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct PromptRole {
+    pub name: String,
+    pub bio: String,
+    pub pronouns: String,
+}
+
+impl From<roles::DbRole> for PromptRole {
+    fn from(r: roles::DbRole) -> Self {
+        PromptRole {
+            name: r.name,
+            bio: r.bio,
+            pronouns: r.pronouns,
+        }
+    }
+}
+
+/// A world info book that isn't in the database (the built-in ones).
+#[derive(Deserialize, Clone)]
+pub struct PromptWorldInfo {
+    pub id: String,
+    pub entries: Vec<WorldInfoEntry>,
+    #[serde(default, alias = "scanDepth")]
+    pub scan_depth: Option<u32>,
+    #[serde(default, alias = "tokenBudget")]
+    pub token_budget: Option<u32>,
+    #[serde(default, alias = "semanticThreshold")]
+    pub semantic_threshold: Option<f32>,
+    /// The cached matcher of a saved book; built-ins get one per prompt.
+    #[serde(skip)]
+    matcher: Option<Arc<KeyMatcher>>,
+}
+
+impl From<DbWorldInfo> for PromptWorldInfo {
+    fn from(wi: DbWorldInfo) -> Self {
+        PromptWorldInfo {
+            matcher: Some(matcher::cached(&wi.id, &wi.entries)),
+            id: wi.id,
+            entries: wi.entries,
+            scan_depth: wi.scan_depth,
+            token_budget: wi.token_budget,
+            semantic_threshold: wi.semantic_threshold,
+        }
+    }
+}
+
+impl PromptWorldInfo {
+    /// `similarity` comes from semantic::similarities.
+    fn lorebook(&self, similarity: Vec<Option<f32>>) -> Lorebook<'_> {
+        Lorebook {
+            id: &self.id,
+            entries: &self.entries,
+            matcher: self
+                .matcher
+                .clone()
+                .unwrap_or_else(|| Arc::new(KeyMatcher::new(&self.entries))),
+            scan_depth: self.scan_depth.unwrap_or(activation::DEFAULT_SCAN_DEPTH),
+            token_budget: self.token_budget,
+            semantic_threshold: self.semantic_threshold,
+            similarity,
+        }
+    }
+}
+
+/// Everything about a prompt that isn't stored with the chat.
+/// New fields carry a camelCase alias, same as AiRequest.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct PromptOptions {
+    /// The user's active role; it's only kept in the frontend.
+    #[serde(alias = "roleId")]
+    pub role_id: Option<String>,
+    /// Reply language; falls back to the "ai_language" setting, then English.
+    pub language: Option<String>,
+    /// A new user message that isn't saved yet, appended after the history.
+    #[serde(alias = "userPrompt")]
+    pub user_prompt: Option<String>,
+    /// Ends the history right before this message (retry / new swipe /
+    /// continuation of it).
+    #[serde(alias = "beforeMessageId")]
+    pub before_message_id: Option<String>,
+    /// Picks the tokenizer for token counts and world info budgets.
+    pub model: Option<String>,
+    /// How many times activated world info is rescanned for further keys;
+    /// falls back to the "world_info_max_recursion" setting, then 0 (off).
+    #[serde(alias = "maxRecursion")]
+    pub max_recursion: Option<u32>,
+    /// Seed for world info probability rolls; by default derived from the
+    /// chat and the message being written, so retries roll the same.
+    pub seed: Option<u64>,
+    /// Notes to place at a depth in the history, next to world info entries
+    /// positioned "at_depth".
+    pub injections: Vec<PromptInjection>,
+    /// How many chunks of summarized messages to recall into the prompt;
+    /// falls back to the "memory_top_k" setting, then 0 (off). Needs an
+    /// embedding model, see semantic::config.
+    #[serde(alias = "memoryTopK")]
+    pub memory_top_k: Option<u32>,
+    /// Token budget of the recalled chunks; falls back to the
+    /// "memory_token_budget" setting, then DEFAULT_MEMORY_BUDGET.
+    #[serde(alias = "memoryTokenBudget")]
+    pub memory_token_budget: Option<u32>,
+    /// Puts what the character remembers from earlier chats into the system
+    /// message; falls back to the "character_memory" setting, then off.
+    #[serde(alias = "characterMemory")]
+    pub character_memory: Option<bool>,
+
+    // --- Fallbacks for data that only exists in the frontend ---
+    // Used when the chat's character, the role or one of the character's world
+    // info books isn't found in the database.
+    pub character: Option<PromptCharacter>,
+    pub role: Option<PromptRole>,
+    #[serde(alias = "worldInfos")]
+    pub world_infos: Vec<PromptWorldInfo>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct PromptMessage {
+    pub role: String,
+    pub content: String,
+}
+
+impl PromptMessage {
+    fn new(role: &str, content: impl Into<String>) -> Self {
+        PromptMessage {
+            role: role.to_string(),
+            content: content.into(),
+        }
+    }
+}
+
+/// Token count of one part of the prompt.
+#[derive(Serialize)]
+pub struct PromptSection {
+    pub name: &'static str,
+    pub tokens: u32,
+}
+
+/// Result of build_prompt.
+#[derive(Serialize)]
+pub struct BuiltPrompt {
+    pub messages: Vec<PromptMessage>,
+    /// Per-section breakdown. Separators and the start marker aren't part of any
+    /// section, so the sections add up to slightly less than total_tokens.
+    pub sections: Vec<PromptSection>,
+    pub total_tokens: u32,
+}
+
+/// An assembled prompt, with the text of each section kept for counting.
+pub(crate) struct Assembled {
+    pub messages: Vec<PromptMessage>,
+    sections: Vec<(&'static str, String)>,
+    /// Sticky / cooldown windows the world info starts; only recorded by
+    /// generations once their reply is kept.
+    pub started: StartedEffects,
+}
+
+/// The chat's character from the database, or the frontend's copy for built-ins.
+fn load_character(app: &AppHandle, chat_id: &str, options: &PromptOptions) -> Result<PromptCharacter, String> {
+    let stored = match chats::get_chat_character_id(app, chat_id)? {
+        Some(id) => characters::get_character(app, &id)?,
+        None => None,
+    };
+    Ok(stored
+        .map(PromptCharacter::from)
+        .or_else(|| options.character.clone())
+        .unwrap_or_default())
+}
+
+fn load_role(app: &AppHandle, options: &PromptOptions) -> Result<PromptRole, String> {
+    let stored = match &options.role_id {
+        Some(id) => roles::get_role(app, id)?,
+        None => None,
+    };
+    Ok(stored
+        .map(PromptRole::from)
+        .or_else(|| options.role.clone())
+        .unwrap_or_default())
+}
+
+/// IDs of the books that apply to a chat with `character`: the character's,
+/// the chat's, the active persona's and the global ones; see
+/// links::active_world_info_ids.
+fn lorebook_ids(
+    app: &AppHandle,
+    chat_id: &str,
+    character: &PromptCharacter,
+    options: &PromptOptions,
+) -> Result<Vec<String>, String> {
+    links::active_world_info_ids(app, &character.world_info_ids, chat_id, options.role_id.as_deref())
+}
+
+/// The world info books of `ids` in that order, stored ones taking
+/// precedence over the frontend's copies.
+fn load_lorebooks(
+    app: &AppHandle,
+    ids: &[String],
+    options: &PromptOptions,
+) -> Result<Vec<PromptWorldInfo>, String> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut stored: Vec<_> = crate::database::world_info::get_world_infos(app.clone())?
+        .into_iter()
+        .filter(|wi| ids.contains(&wi.id))
+        .map(PromptWorldInfo::from)
+        .collect();
+    let builtin: Vec<_> = options
+        .world_infos
+        .iter()
+        .filter(|wi| ids.contains(&wi.id) && !stored.iter().any(|s| s.id == wi.id))
+        .cloned()
+        .collect();
+
+    stored.extend(builtin);
+    stored.sort_by_key(|wi| ids.iter().position(|id| *id == wi.id));
+    Ok(stored)
+}
+
+fn language(app: &AppHandle, options: &PromptOptions) -> Result<String, String> {
+    if let Some(language) = options.language.as_ref().filter(|l| !l.is_empty()) {
+        return Ok(language.clone());
+    }
+    Ok(settings::get_setting(app, "ai_language")?
+        .filter(|l| !l.is_empty())
+        .unwrap_or_else(|| DEFAULT_LANGUAGE.to_string()))
+}
+
+fn max_recursion(app: &AppHandle, options: &PromptOptions) -> Result<u32, String> {
+    if let Some(steps) = options.max_recursion {
+        return Ok(steps);
+    }
+    Ok(settings::get_setting(app, "world_info_max_recursion")?
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(0))
+}
+
+/// Long-term memory limits from the options or the settings.
+fn recall_limits(app: &AppHandle, options: &PromptOptions) -> Result<RecallLimits, String> {
+    let setting = |key: &str| -> Result<Option<u32>, String> {
+        Ok(settings::get_setting(app, key)?.and_then(|v| v.trim().parse().ok()))
+    };
+    let top_k = match options.memory_top_k {
+        Some(top_k) => top_k,
+        None => setting("memory_top_k")?.unwrap_or(0),
+    };
+    let token_budget = match options.memory_token_budget {
+        Some(budget) => budget,
+        None => setting("memory_token_budget")?.unwrap_or(DEFAULT_MEMORY_BUDGET),
+    };
+    Ok(RecallLimits {
+        top_k,
+        token_budget,
+        model: options.model.clone().unwrap_or_default(),
+    })
+}
+
+/// Runs the activation engine for the message at `position` of `chat_id`
+/// with the options' model, recursion limit and seed. Without a chat
+/// (`chat_id` empty) there are no timed effects.
+fn activate_world_info<'a>(
+    app: &AppHandle,
+    chat_id: &str,
+    options: &PromptOptions,
+    lorebooks: &[Lorebook<'a>],
+    scan_messages: &'a [&'a str],
+    position: u32,
+) -> Result<(Timeline, Activation<'a>), String> {
+    let timeline = if chat_id.is_empty() {
+        Timeline { position, ..Default::default() }
+    } else {
+        timed_effects::load_timeline(app, chat_id, position)?
+    };
+    let activation = activation::activate(
+        lorebooks,
+        scan_messages,
+        options.model.as_deref().unwrap_or_default(),
+        max_recursion(app, options)?,
+        &timeline,
+        options.seed.unwrap_or_else(|| activation::chat_seed(chat_id, position)),
+    );
+    Ok((timeline, activation))
+}
+
+/// Activated entries as they go into the prompt.
+struct RenderedWorldInfo {
+    /// The "before" and "after" entries as labelled sections, for context_block().
+    sections: Vec<String>,
+    /// Entries positioned "at_depth", for inject().
+    at_depth: Vec<Injection>,
+}
+
+fn render_world_info(activated: &[&WorldInfoEntry], rp: &dyn Fn(&str) -> String) -> RenderedWorldInfo {
+    let sections = [("world_info_before", "before"), ("world_info_after", "after")]
+        .into_iter()
+        .map(|(label, position)| {
+            let content = activated
+                .iter()
+                .filter(|e| e.position == position)
+                .map(|e| e.content.trim())
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            (label, content)
+        })
+        .filter(|(_, content)| !content.is_empty())
+        .map(|(label, content)| format_section(label, &rp(&content)))
+        .collect::<Vec<_>>();
+
+    let at_depth = activated
+        .iter()
+        .filter(|e| e.position == "at_depth")
+        .map(|e| Injection { depth: e.depth, role: e.role, order: e.order, content: rp(&e.content) })
+        .collect();
+    RenderedWorldInfo { sections, at_depth }
+}
+
+/// Sections for the next reply only, in one block attached to the last user
+/// turn; empty if there are none.
+fn context_block(sections: &[String], user_name: &str) -> String {
+    if sections.is_empty() {
+        return String::new();
+    }
+    format!(
+        "[Roleplay context for the next reply only — not something {} said, do not treat it as dialogue]\n{}\n[End context]",
+        user_name,
+        sections.join("\n\n"),
+    )
+}
+
+struct HistoryMessage {
+    id: String,
+    role: String,
+    content: String,
+}
+
+/// The chat's messages, cut where the options say. Replies still being
+/// streamed into the chat are left out, and assistant messages have their
+/// thinking blocks stripped.
+fn load_history(app: &AppHandle, chat_id: &str, options: &PromptOptions) -> Result<Vec<HistoryMessage>, String> {
+    let mut stored = messages::get_messages(app.clone(), chat_id.to_string())?;
+
+    let position = |id: &String| {
+        stored
+            .iter()
+            .position(|m| m.id == *id)
+            .ok_or_else(|| format!("Message {} not found in chat {}", id, chat_id))
+    };
+    if let Some(id) = &options.before_message_id {
+        let end = position(id)?;
+        stored.truncate(end);
+    }
+
+    Ok(stored
+        .into_iter()
+        .filter(|m| m.generation_status != messages::GenerationStatus::Streaming.as_str())
+        .map(|m| {
+            let content = if m.role == "assistant" { strip_thinking(&m.content) } else { m.content };
+            HistoryMessage { id: m.id, role: m.role, content }
+        })
+        .collect())
+}
+
+/// "Name (pronouns)", or just the name the user goes by.
+fn user_identity(role: &PromptRole, user_name: &str) -> String {
+    let mut parts = Vec::new();
+    if user_name != DEFAULT_USER_NAME {
+        parts.push(user_name.to_string());
+    }
+    if !role.pronouns.is_empty() {
+        parts.push(format!("({})", role.pronouns));
+    }
+    if parts.is_empty() {
+        user_name.to_string()
+    } else {
+        parts.join(" ")
+    }
+}
+
+fn format_section(label: &str, content: &str) -> String {
+    format!("{}\n{}", section_label(label), content)
+}
+
+/// Builds the prompt for `chat_id` the same way buildApiMessages() does in the
+/// frontend: one system message (instructions, card, role, what the character
+/// remembers from other chats, summary), the messages not yet covered by the
+/// summary, and triggered world info and recalled memory (see memory::recall)
+/// attached to the last user turn only, so the system message stays
+/// cacheable. World info positioned "at_depth" and the options' injections go
+/// into the history instead; see inject().
+pub(crate) async fn assemble(app: &AppHandle, chat_id: &str, options: &PromptOptions) -> Result<Assembled, String> {
+    let character = load_character(app, chat_id, options)?;
+    let role = load_role(app, options)?;
+    let language = language(app, options)?;
+    let summary = chats::get_summary_meta(app.clone(), chat_id.to_string())?;
+    let history = load_history(app, chat_id, options)?;
+    let user_prompt = options.user_prompt.clone().unwrap_or_default();
+
+    let char_name = if character.name.is_empty() { DEFAULT_CHAR_NAME } else { &character.name };
+    let user_name = if role.name.is_empty() { DEFAULT_USER_NAME } else { &role.name };
+    let rp = |text: &str| replace_placeholders(text.trim(), char_name, user_name);
+
+    let instructions = format!(
+        "You are {char}. Reply in {lang}.\n\
+         You are speaking with {identity}. React to them as {char} naturally would throughout the entire conversation.\n\
+         Stay fully in character. The user has an active role — you must perceive and react to it consistently.\n\
+         If a message is prefixed with [OOC:], treat it as a director's instruction. Do NOT respond as {char}. \
+         Silently incorporate it into your next in-character response, then seamlessly return to character.",
+        char = char_name,
+        lang = language,
+        identity = user_identity(&role, user_name),
+    );
+
+    let card = [
+        ("description", &character.desc),
+        ("personality", &character.personality),
+        ("scenario", &character.scenario),
+        ("example_dialog", &character.mes_example),
+    ]
+    .into_iter()
+    .filter(|(_, content)| !content.trim().is_empty())
+    .map(|(label, content)| format_section(label, &rp(content)))
+    .collect::<Vec<_>>()
+    .join("\n\n");
+
+    let persona = if role.bio.trim().is_empty() {
+        String::new()
+    } else {
+        let bio = rp(&role.bio)
+            + "\n\nOnly address them by name in-character if it has been introduced in the roleplay.";
+        format_section("user_role", &bio)
+    };
+
+    let notes = character_memory::memory_notes(app, chat_id, options)?;
+    let remembered = if notes.is_empty() {
+        String::new()
+    } else {
+        let intro = format!("What {} remembers from earlier conversations:", char_name);
+        format_section("character_memory", &format!("{}\n{}", intro, notes))
+    };
+
+    let mut system = [instructions.as_str(), card.as_str(), persona.as_str(), remembered.as_str()]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let summary_text = summary.summary.unwrap_or_default();
+    if !summary_text.is_empty() {
+        system.push_str(&format!("\n\n[Previous conversation summary:\n{}]", summary_text));
+    }
+
+    // Only messages that haven't been compressed into the summary yet.
+    let unsummarized_from = summary
+        .last_id
+        .and_then(|last| history.iter().position(|m| m.id == last))
+        .map_or(0, |i| i + 1);
+    let unsummarized = &history[unsummarized_from..];
+
+    let mut prompt = vec![PromptMessage::new("system", system)];
+    prompt.extend(unsummarized.iter().map(|m| PromptMessage::new(&m.role, m.content.as_str())));
+    if !user_prompt.is_empty() {
+        prompt.push(PromptMessage::new("user", user_prompt.as_str()));
+    }
+
+    if prompt.iter().find(|m| m.role != "system").is_some_and(|m| m.role == "assistant") {
+        let after_system = prompt.iter().rposition(|m| m.role == "system").map_or(0, |i| i + 1);
+        prompt.insert(after_system, PromptMessage::new("user", START_ROLEPLAY_MARKER));
+    }
+
+    // Keys are matched against the latest messages even if they're already
+    // summarized, and against the pending user message.
+    let scan_messages: Vec<&str> = history
+        .iter()
+        .map(|m| m.content.as_str())
+        .chain(Some(user_prompt.as_str()).filter(|p| !p.is_empty()))
+        .collect();
+    let books = load_lorebooks(app, &lorebook_ids(app, chat_id, &character, options)?, options)?;
+    let similarity = semantic::similarities(app, &books, &scan_messages).await;
+    let lorebooks: Vec<Lorebook> = books.iter().zip(similarity).map(|(b, s)| b.lorebook(s)).collect();
+    // Timed effects count in messages before the one being written.
+    let position = scan_messages.len() as u32;
+    let (timeline, activation) =
+        activate_world_info(app, chat_id, options, &lorebooks, &scan_messages, position)?;
+    let started = StartedEffects::new(chat_id, &timeline, &activation.started);
+    let activated = activation.entries;
+
+    // Older details the summary no longer holds, next to the world info.
+    let speaker = |role: &str| match role {
+        "user" => user_name.to_string(),
+        "assistant" => char_name.to_string(),
+        other => other.to_string(),
+    };
+    let chunks = memory::chunks(&history, unsummarized_from, &speaker);
+    let recalled = memory::recall(app, chat_id, chunks, &scan_messages, &recall_limits(app, options)?).await;
+    let memory_text = recalled.join("\n\n");
+    let memory_section = (!recalled.is_empty()).then(|| format_section("earlier_events", &memory_text));
+
+    let world_info = render_world_info(&activated, &rp);
+    let attached = context_block(
+        &memory_section.into_iter().chain(world_info.sections.iter().cloned()).collect::<Vec<_>>(),
+        user_name,
+    );
+    if !attached.is_empty() {
+        match prompt.iter_mut().rev().find(|m| m.role == "user") {
+            Some(last_user) => last_user.content = format!("{}\n\n{}", attached, last_user.content),
+            // No user turn to attach to — a standalone message so it isn't lost.
+            None => prompt.push(PromptMessage::new("user", attached.as_str())),
+        }
+    }
+
+    // World info entries come before notes sharing their depth, role and order.
+    let depth_world_info = world_info.at_depth;
+    let notes: Vec<Injection> = options
+        .injections
+        .iter()
+        .map(|i| Injection { depth: i.depth, role: i.role, order: i.order, content: rp(&i.content) })
+        .collect();
+    let join = |injections: &[Injection]| {
+        injections
+            .iter()
+            .map(|i| i.content.as_str())
+            .filter(|c| !c.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    };
+    let world_info = [world_info.sections.join("\n\n"), join(&depth_world_info)]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let notes_text = join(&notes);
+    let prompt = inject(prompt, depth_world_info.into_iter().chain(notes).collect());
+
+    let history_text = unsummarized
+        .iter()
+        .map(|m| m.content.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(Assembled {
+        messages: prompt,
+        sections: vec![
+            ("instructions", instructions),
+            ("character", card),
+            ("persona", persona),
+            ("character_memory", remembered),
+            ("summary", summary_text),
+            ("world_info", world_info),
+            ("memory", memory_text),
+            ("notes", notes_text),
+            ("history", history_text),
+            ("user_prompt", user_prompt),
+        ],
+        started,
+    })
+}
+
+/// Builds the final messages array for a chat straight from the database, with
+/// a token count per section (instructions, character, persona,
+/// character_memory, summary, world_info, memory, notes, history, user_prompt) for the context usage display.
+#[tauri::command]
+pub async fn build_prompt(app: AppHandle, chat_id: String, options: Option<PromptOptions>) -> Result<BuiltPrompt, String> {
+    let options = options.unwrap_or_default();
+    let assembled = assemble(&app, &chat_id, &options).await?;
+    let model = options.model.unwrap_or_default();
+
+    let sections = assembled
+        .sections
+        .into_iter()
+        .map(|(name, text)| PromptSection {
+            name,
+            tokens: count_tokens(text, model.clone()),
+        })
+        .collect();
+    let total_tokens = assembled
+        .messages
+        .iter()
+        .map(|m| count_tokens(m.content.clone(), model.clone()))
+        .sum();
+
+    Ok(BuiltPrompt {
+        messages: assembled.messages,
+        sections,
+        total_tokens,
+    })
+}
@@ -0,0 +1,159 @@
+use std::collections::{HashMap, HashSet};
+use tauri::AppHandle;
+
+use super::PromptWorldInfo;
+use crate::ai::embeddings::{cosine_similarity, embed, EmbeddingConfig};
+use crate::ai::Provider;
+use crate::database::world_info::activation::DEFAULT_SCAN_DEPTH;
+use crate::database::world_info::WorldInfoEntry;
+use crate::database::{embeddings, settings};
+
+/// Entry contents sent per embedding request; keeps requests to local
+/// servers small when a large book is embedded for the first time.
+const BATCH_SIZE: usize = 64;
+
+/// The embedding endpoint from the settings, None if embeddings are off
+/// ("embedding_model" unset). "embedding_url" defaults to the chat API's URL,
+/// and its key with it, but only for OpenAI-compatible providers; the others
+/// have no /embeddings route. Shared with memory recall.
+pub(super) fn config(app: &AppHandle) -> Result<Option<EmbeddingConfig>, String> {
+    let setting = |key: &str| -> Result<String, String> {
+        Ok(settings::get_setting(app, key)?.unwrap_or_default().trim().to_string())
+    };
+    let model = setting("embedding_model")?;
+    if model.is_empty() {
+        return Ok(None);
+    }
+    let url = setting("embedding_url")?;
+    let (url, api_key) = if url.is_empty() {
+        let provider = setting("api_provider")?;
+        let provider: Provider = serde_json::from_value(provider.into()).unwrap_or_default();
+        if provider != Provider::OpenAi {
+            return Ok(None);
+        }
+        (setting("api_url")?, setting("api_key")?)
+    } else {
+        (url, setting("embedding_api_key")?)
+    };
+    if url.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(EmbeddingConfig { url, api_key, model }))
+}
+
+/// Vectors for `contents`, from the cache where possible; the rest are
+/// embedded and cached.
+pub(super) async fn content_vectors(
+    app: &AppHandle,
+    config: &EmbeddingConfig,
+    contents: &[&str],
+) -> Result<HashMap<String, Vec<f32>>, String> {
+    let hashes: Vec<String> = contents.iter().map(|c| embeddings::content_hash(c)).collect();
+    let mut vectors = embeddings::get_embeddings(app, &config.model, &hashes)?;
+
+    let mut seen = HashSet::new();
+    let missing: Vec<(&String, &str)> = hashes
+        .iter()
+        .zip(contents.iter().copied())
+        .filter(|(hash, _)| !vectors.contains_key(*hash) && seen.insert(*hash))
+        .collect();
+    for batch in missing.chunks(BATCH_SIZE) {
+        let inputs: Vec<&str> = batch.iter().map(|(_, content)| *content).collect();
+        let embedded: Vec<(String, Vec<f32>)> = batch
+            .iter()
+            .map(|(hash, _)| (*hash).clone())
+            .zip(embed(config, &inputs).await?)
+            .collect();
+        embeddings::store_embeddings(app, &config.model, &embedded)?;
+        vectors.extend(embedded);
+    }
+    Ok(vectors)
+}
+
+/// Similarity of every entry of the books with a semantic threshold to the
+/// messages each entry is scanned against (the last `scan_depth` of
+/// `scan_messages`), by book, then entry. Books without a threshold, disabled
+/// or empty entries, and entries with nothing to scan get no scores.
+async fn compute(
+    app: &AppHandle,
+    config: &EmbeddingConfig,
+    books: &[PromptWorldInfo],
+    scan_messages: &[&str],
+) -> Result<Vec<Vec<Option<f32>>>, String> {
+    let depth = |book: &PromptWorldInfo, entry: &WorldInfoEntry| {
+        entry.scan_depth.or(book.scan_depth).unwrap_or(DEFAULT_SCAN_DEPTH)
+    };
+    let scored = |book: &PromptWorldInfo| book.semantic_threshold.is_some();
+    let window = |depth: u32| &scan_messages[scan_messages.len().saturating_sub(depth as usize)..];
+    // A blank window (scan depth 0, or only empty messages) can't be embedded;
+    // those entries are left to their keywords.
+    let has_window = |depth: u32| window(depth).iter().any(|m| !m.trim().is_empty());
+
+    let mut contents = Vec::new();
+    let mut depths = Vec::new();
+    for book in books.iter().filter(|b| scored(b)) {
+        for entry in book
+            .entries
+            .iter()
+            .filter(|e| e.enabled && !e.content.trim().is_empty() && has_window(depth(book, e)))
+        {
+            contents.push(entry.content.trim());
+            depths.push(depth(book, entry));
+        }
+    }
+    if contents.is_empty() {
+        return Ok(vec![Vec::new(); books.len()]);
+    }
+
+    // The context changes with every message, so it isn't cached; one
+    // embedding per distinct scan depth.
+    depths.sort_unstable();
+    depths.dedup();
+    let windows: Vec<String> = depths
+        .iter()
+        .map(|&d| window(d).join("\n"))
+        .collect();
+    let window_refs: Vec<&str> = windows.iter().map(String::as_str).collect();
+    let context: HashMap<u32, Vec<f32>> = depths.into_iter().zip(embed(config, &window_refs).await?).collect();
+    let vectors = content_vectors(app, config, &contents).await?;
+
+    Ok(books
+        .iter()
+        .map(|book| {
+            if !scored(book) {
+                return Vec::new();
+            }
+            book.entries
+                .iter()
+                .map(|entry| {
+                    let content = vectors.get(&embeddings::content_hash(entry.content.trim()))?;
+                    let context = context.get(&depth(book, entry))?;
+                    Some(cosine_similarity(context, content))
+                })
+                .collect()
+        })
+        .collect())
+}
+
+/// Scores for Lorebook::similarity, see compute(). Semantic activation is an
+/// extra, so if the endpoint can't be reached the books fall back to their
+/// keywords.
+pub(super) async fn similarities(
+    app: &AppHandle,
+    books: &[PromptWorldInfo],
+    scan_messages: &[&str],
+) -> Vec<Vec<Option<f32>>> {
+    let none = || vec![Vec::new(); books.len()];
+    if scan_messages.is_empty() || !books.iter().any(|b| b.semantic_threshold.is_some()) {
+        return none();
+    }
+    let result = match config(app) {
+        Ok(Some(config)) => compute(app, &config, books, scan_messages).await,
+        Ok(None) => return none(),
+        Err(e) => Err(e),
+    };
+    result.unwrap_or_else(|e| {
+        eprintln!("[WorldInfo] Semantic activation skipped: {}", e);
+        none()
+    })
+}
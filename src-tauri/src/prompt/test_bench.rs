@@ -0,0 +1,136 @@
+use serde::Serialize;
+use tauri::AppHandle;
+
+use super::semantic;
+use super::text::replace_placeholders;
+use super::{
+    activate_world_info, context_block, load_character, load_history, load_lorebooks, load_role,
+    lorebook_ids, render_world_info, PromptOptions, DEFAULT_CHAR_NAME, DEFAULT_USER_NAME,
+};
+use crate::database::world_info::activation::{EntryStatus, Lorebook, Trigger};
+use crate::database::world_info::InjectionRole;
+
+/// What the engine made of one entry.
+#[derive(Serialize)]
+pub struct EntryTestResult {
+    pub book_id: String,
+    pub entry_id: String,
+    pub comment: String,
+    pub status: EntryStatus,
+    /// What made it a candidate; None if it never triggered.
+    pub trigger: Option<Trigger>,
+    pub scan_depth: u32,
+    /// Recursion step it triggered in; 0 = the messages themselves.
+    pub step: Option<u32>,
+    /// Counted once it got past probability and inclusion groups.
+    pub tokens: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct DepthInjection {
+    pub depth: u32,
+    pub role: InjectionRole,
+    pub content: String,
+}
+
+#[derive(Serialize)]
+pub struct WorldInfoTestResult {
+    /// Every entry of the tested books, in book order, then entry order.
+    pub entries: Vec<EntryTestResult>,
+    /// How many messages were scanned, the text included.
+    pub scanned_messages: usize,
+    /// The block prepended to the last user turn.
+    pub attached: String,
+    /// Entries positioned "at_depth", by their order.
+    pub at_depth: Vec<DepthInjection>,
+}
+
+/// Runs world info activation without generating anything, to see why an
+/// entry did or didn't fire. Scans the chat's history (cut per `options`, like
+/// a real prompt) and/or `text` as if it were the next user message. Tests
+/// `world_info_ids`, or every book the chat would use if that's empty. Timed
+/// effects are read from the chat but never saved.
+#[tauri::command]
+pub async fn test_world_info(
+    app: AppHandle,
+    chat_id: Option<String>,
+    text: Option<String>,
+    world_info_ids: Vec<String>,
+    options: Option<PromptOptions>,
+) -> Result<WorldInfoTestResult, String> {
+    let options = options.unwrap_or_default();
+    let chat_id = chat_id.unwrap_or_default();
+    let text = text.unwrap_or_default();
+    if chat_id.is_empty() && text.trim().is_empty() {
+        return Err("Nothing to scan: pass a chat or some text.".to_string());
+    }
+
+    let history = if chat_id.is_empty() {
+        Vec::new()
+    } else {
+        load_history(&app, &chat_id, &options)?
+    };
+    let scan_messages: Vec<&str> = history
+        .iter()
+        .map(|m| m.content.as_str())
+        .chain(Some(text.as_str()).filter(|t| !t.trim().is_empty()))
+        .collect();
+
+    let character = if chat_id.is_empty() {
+        options.character.clone().unwrap_or_default()
+    } else {
+        load_character(&app, &chat_id, &options)?
+    };
+    let role = load_role(&app, &options)?;
+    let ids = if world_info_ids.is_empty() {
+        lorebook_ids(&app, &chat_id, &character, &options)?
+    } else {
+        world_info_ids
+    };
+    let books = load_lorebooks(&app, &ids, &options)?;
+    let similarity = semantic::similarities(&app, &books, &scan_messages).await;
+    let lorebooks: Vec<Lorebook> = books.iter().zip(similarity).map(|(b, s)| b.lorebook(s)).collect();
+
+    let (_, activation) = activate_world_info(
+        &app,
+        &chat_id,
+        &options,
+        &lorebooks,
+        &scan_messages,
+        scan_messages.len() as u32,
+    )?;
+
+    let char_name = if character.name.is_empty() { DEFAULT_CHAR_NAME } else { &character.name };
+    let user_name = if role.name.is_empty() { DEFAULT_USER_NAME } else { &role.name };
+    let rp = |text: &str| replace_placeholders(text.trim(), char_name, user_name);
+    let rendered = render_world_info(&activation.entries, &rp);
+
+    let entries = activation
+        .trace
+        .into_iter()
+        .map(|t| {
+            let entry = &books[t.book].entries[t.entry];
+            EntryTestResult {
+                book_id: books[t.book].id.clone(),
+                entry_id: entry.id.clone(),
+                comment: entry.comment.clone(),
+                status: t.status,
+                trigger: t.trigger,
+                scan_depth: t.scan_depth,
+                step: t.step,
+                tokens: t.tokens,
+            }
+        })
+        .collect();
+
+    Ok(WorldInfoTestResult {
+        entries,
+        scanned_messages: scan_messages.len(),
+        attached: context_block(&rendered.sections, user_name),
+        at_depth: rendered
+            .at_depth
+            .into_iter()
+            .map(|i| DepthInjection { depth: i.depth, role: i.role, content: i.content })
+            .collect(),
+    })
+}
@@ -0,0 +1,84 @@
+/// Finds `needle` in `haystack`, ignoring ASCII case. The tags and placeholders
+/// searched for are plain ASCII, so byte offsets stay valid for the original.
+fn find_ignore_case(haystack: &str, needle: &str, from: usize) -> Option<usize> {
+    let hay = haystack.as_bytes();
+    let needle = needle.as_bytes();
+    if needle.is_empty() || hay.len() < needle.len() {
+        return None;
+    }
+    (from..=hay.len() - needle.len())
+        .find(|&i| hay[i..i + needle.len()].eq_ignore_ascii_case(needle))
+}
+
+fn starts_with_ignore_case(text: &str, prefix: &str) -> bool {
+    text.len() >= prefix.len()
+        && text.as_bytes()[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes())
+}
+
+/// Removes a tag-delimited block: complete blocks, then an orphaned closing tag
+/// (keeps what follows it) and an orphaned opening tag (keeps what precedes it).
+fn strip_tag_block(content: &str, open: &str, close: &str) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut pos = 0;
+    while let Some(start) = find_ignore_case(content, open, pos) {
+        let Some(end) = find_ignore_case(content, close, start + open.len()) else {
+            break;
+        };
+        result.push_str(&content[pos..start]);
+        pos = end + close.len();
+    }
+    result.push_str(&content[pos..]);
+
+    if let Some(idx) = result.find(close) {
+        result.drain(..idx + close.len());
+    }
+    if let Some(idx) = result.find(open) {
+        result.truncate(idx);
+    }
+    result
+}
+
+/// Removes <think>…</think> and <|channel>…<channel|> blocks from a finished
+/// assistant message before it goes back into a prompt. Same rules as
+/// stripThinkingContent() in the frontend.
+pub(crate) fn strip_thinking(content: &str) -> String {
+    let result = strip_tag_block(content, "<think>", "</think>");
+    let result = strip_tag_block(&result, "<|channel>", "<channel|>");
+    result.trim_start().to_string()
+}
+
+/// Replaces {{char}} and {{user}}, in any casing.
+pub(crate) fn replace_placeholders(text: &str, char_name: &str, user_name: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut pos = 0;
+    while let Some(start) = text[pos..].find("{{").map(|i| pos + i) {
+        result.push_str(&text[pos..start]);
+        if starts_with_ignore_case(&text[start..], "{{char}}") {
+            result.push_str(char_name);
+            pos = start + "{{char}}".len();
+        } else if starts_with_ignore_case(&text[start..], "{{user}}") {
+            result.push_str(user_name);
+            pos = start + "{{user}}".len();
+        } else {
+            result.push_str("{{");
+            pos = start + 2;
+        }
+    }
+    result.push_str(&text[pos..]);
+    result
+}
+
+/// "world_info_before" -> "World Info Before", as the frontend's section labels.
+pub(crate) fn section_label(label: &str) -> String {
+    label
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -1,6 +1,8 @@
 import { invoke } from '@tauri-apps/api/core';
 
-export type WiPosition = 'before' | 'after';
+export type WiPosition = 'before' | 'after' | 'at_depth';
+export type WiRole = 'system' | 'user' | 'assistant';
+export type WiSelectiveLogic = 'and_any' | 'and_all' | 'not_any' | 'not_all';
 
 export interface WorldInfoEntry {
   id:       string;
@@ -9,12 +11,39 @@ export interface WorldInfoEntry {
   enabled:  boolean;
   comment:  string;
   position: WiPosition;
+  secondary_keys?:    string[];
+  selective_logic?:   WiSelectiveLogic;
+  case_sensitive?:    boolean;
+  match_whole_words?: boolean;
+  scan_depth?:        number | null;
+  exclude_recursion?: boolean;
+  prevent_recursion?: boolean;
+  /** Messages the entry stays active after triggering. */
+  sticky?:            number;
+  /** Messages after that before it can trigger again. */
+  cooldown?:          number;
+  /** Messages the chat needs before the entry can trigger. */
+  delay?:             number;
+  constant?:          boolean;
+  /** Chance in percent that the entry is inserted once triggered. */
+  probability?:       number;
+  /** Activated entries are inserted from lowest to highest order. */
+  order?:             number;
+  /** Inclusion group; only its highest weighted triggered entry is inserted. */
+  group?:             string;
+  group_weight?:      number;
+  /** For 'at_depth': messages from the end of the chat, 0 = after the last one. */
+  depth?:             number;
+  role?:              WiRole;
 }
 
 export interface WorldInfoFormData {
-  name:        string;
-  description: string;
-  entries:     WorldInfoEntry[];
+  name:          string;
+  description:   string;
+  entries:       WorldInfoEntry[];
+  scan_depth?:   number | null;
+  token_budget?: number | null;
+  semantic_threshold?: number | null;
 }
 
 export function createEmptyEntry(): WorldInfoEntry {
@@ -25,9 +54,50 @@ export function createEmptyEntry(): WorldInfoEntry {
     enabled:  true,
     comment:  '',
     position: 'before',
+    secondary_keys:    [],
+    selective_logic:   'and_any',
+    case_sensitive:    false,
+    match_whole_words: false,
+    scan_depth:        null,
+    exclude_recursion: false,
+    prevent_recursion: false,
+    sticky:            0,
+    cooldown:          0,
+    delay:             0,
+    constant:          false,
+    probability:       100,
+    order:             0,
+    group:             '',
+    group_weight:      100,
+    depth:             4,
+    role:              'system',
   };
 }
 
+/** Same default as activation::DEFAULT_SCAN_DEPTH on the backend. */
+export const DEFAULT_SCAN_DEPTH = 10;
+
+/** Comma-separated keys as typed into the editor. */
+export function parseKeys(raw: string): string[] {
+  return raw.split(',').map(k => k.trim()).filter(Boolean);
+}
+
+/** Whole number from a number input, clamped to [min, max]; empty or invalid = null. */
+export function parseOptionalInt(raw: string, min = 0, max = Number.MAX_SAFE_INTEGER): number | null {
+  if (raw.trim() === '') return null;
+  const n = Math.round(Number(raw));
+  return Number.isFinite(n) ? Math.min(max, Math.max(min, n)) : null;
+}
+
+/** Returns `entries` with the entry `id` changed by `patch`. */
+export function patchEntry(
+  entries: WorldInfoEntry[],
+  id: string,
+  patch: Partial<WorldInfoEntry>,
+): WorldInfoEntry[] {
+  return entries.map(e => e.id === id ? { ...e, ...patch } : e);
+}
+
 export async function createWorldInfo(data: WorldInfoFormData): Promise<string> {
   return invoke<string>('create_world_info', { payload: data });
 }
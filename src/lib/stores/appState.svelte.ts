@@ -1,4 +1,11 @@
+/** Wire protocol spoken by the backend (see `Provider` in ai/provider.rs). */
+export type ApiProvider = 'openai' | 'anthropic' | 'ollama' | 'koboldcpp';
+
+/** Instruct format for text-completion mode (see `InstructTemplate` in ai/templates.rs). */
+export type InstructTemplate = 'chatml' | 'llama3' | 'mistral' | 'gemma' | 'alpaca' | 'custom';
+
 export interface ApiSettings {
+  provider: ApiProvider;
   url: string;
   apiKey: string;
   model: string;
@@ -15,6 +22,23 @@ export interface ApiSettings {
   contextLimit: number;
   thinkingBudget: number;
   customMode: boolean;
+  textCompletion: boolean;
+  instructTemplate: InstructTemplate;
+  customTemplate: string;
+  /** Embedding model for semantic world info and memory recall; empty = off. */
+  embeddingModel: string;
+  /** Empty = the chat endpoint (OpenAI-compatible providers only). */
+  embeddingUrl: string;
+  embeddingApiKey: string;
+  /** Summarized chunks recalled per prompt; 0 = off. Needs an embedding model. */
+  memoryTopK: number;
+  memoryTokenBudget: number;
+  /** Times activated world info is rescanned for further keys; 0 = off. */
+  worldInfoMaxRecursion: number;
+  /** Puts what the character remembers from earlier chats into new ones. */
+  characterMemory: boolean;
+  /** Days the requests shown in the prompt inspector are kept; 0 = not recorded. */
+  promptInspectorRetentionDays: number;
 }
 
 export const appState = $state({
@@ -25,6 +49,7 @@ export const appState = $state({
   isOnboarding: false,
   pendingUiLocale: '',
   apiSettings: {
+    provider: "openai",
     url: "http://127.0.0.1:1234/v1",
     apiKey: "",
     model: "",
@@ -41,5 +66,16 @@ export const appState = $state({
     frequencyPenalty: 0,
     contextLimit: 4096,
     customMode: false,
+    textCompletion: false,
+    instructTemplate: "chatml",
+    customTemplate: "",
+    embeddingModel: "",
+    embeddingUrl: "",
+    embeddingApiKey: "",
+    memoryTopK: 0,
+    memoryTokenBudget: 600,
+    promptInspectorRetentionDays: 30,
+    worldInfoMaxRecursion: 0,
+    characterMemory: false,
   } as ApiSettings
 });
\ No newline at end of file
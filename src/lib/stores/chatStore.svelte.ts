@@ -3,6 +3,7 @@ import { appState } from './appState.svelte';
 import { characterState } from './characterStore.svelte';
 import { roleState } from './roleStore.svelte';
 import { getLocale } from '$lib/paraglide/runtime';
+import type { GenerationStats } from '$lib/utils/chatApi';
 
 export interface Message {
     id?: string;
@@ -11,6 +12,12 @@ export interface Message {
     content: string;
     swipe_variants: string[];
     swipe_index: number;
+    /** Parallel to swipe_variants; null where a variant has no stats. */
+    swipe_stats: (GenerationStats | null)[];
+    /** Parallel to swipe_variants; reasoning text behind each variant, if any. */
+    swipe_reasoning: (string | null)[];
+    /** Set by backend-owned generations (generate_into_chat). */
+    generation_status?: 'streaming' | 'complete' | 'interrupted';
 }
 
 export interface Conversation {
@@ -212,6 +219,12 @@ export async function loadMessages(chatId: string) {
                 ? JSON.parse(row.swipe_variants)
                 : (row.swipe_variants ?? [row.content]),
             swipe_index: row.swipe_index ?? 0,
+            swipe_stats: typeof row.swipe_stats === 'string'
+                ? JSON.parse(row.swipe_stats)
+                : (row.swipe_stats ?? []),
+            swipe_reasoning: typeof row.swipe_reasoning === 'string'
+                ? JSON.parse(row.swipe_reasoning)
+                : (row.swipe_reasoning ?? []),
         }));
         chatState.activeChatId = chatId;
         
@@ -244,6 +257,12 @@ export async function loadMoreMessages() {
                 ? JSON.parse(row.swipe_variants)
                 : (row.swipe_variants ?? [row.content]),
             swipe_index: row.swipe_index ?? 0,
+            swipe_stats: typeof row.swipe_stats === 'string'
+                ? JSON.parse(row.swipe_stats)
+                : (row.swipe_stats ?? []),
+            swipe_reasoning: typeof row.swipe_reasoning === 'string'
+                ? JSON.parse(row.swipe_reasoning)
+                : (row.swipe_reasoning ?? []),
         }));
 
         // Prepend older messages at the beginning
@@ -252,20 +271,32 @@ export async function loadMoreMessages() {
     } catch (e) { console.error(e); }
 }
 
-export async function addMessage(role: 'user' | 'assistant', content: string) {
+export async function addMessage(
+    role: 'user' | 'assistant',
+    content: string,
+    stats: GenerationStats | null = null,
+    reasoning: string | null = null,
+    generationId: string | null = null,
+) {
     const chatId = chatState.activeChatId;
     if (!chatId) return;
     try {
-        await invoke('add_message', { chatId, role, content });
+        await invoke('add_message', { chatId, role, content, stats, reasoning, generationId });
         await loadAllConversations();
         await loadMessages(chatId);
     } catch (e) { console.error(e); }
 }
 
-export async function addSwipeVariant(messageId: string, content: string): Promise<void> {
+export async function addSwipeVariant(
+    messageId: string,
+    content: string,
+    stats: GenerationStats | null = null,
+    reasoning: string | null = null,
+    generationId: string | null = null,
+): Promise<void> {
     const chatId = chatState.activeChatId;
     try {
-        await invoke('add_swipe_variant', { messageId, content });
+        await invoke('add_swipe_variant', { messageId, content, stats, reasoning, generationId });
         if (chatId) await loadMessages(chatId);
     } catch (e) { console.error(e); }
 }
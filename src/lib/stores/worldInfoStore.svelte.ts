@@ -7,6 +7,10 @@ export interface WorldInfo {
   name:        string;
   description: string;
   entries:     WorldInfoEntry[];
+  scan_depth?:   number | null;
+  token_budget?: number | null;
+  /** Cosine similarity (0–1) at which entries activate without keys; null = keywords only. */
+  semantic_threshold?: number | null;
   created_at?: string;
 }
 
@@ -21,4 +25,30 @@ export async function loadWorldInfos(): Promise<void> {
   } catch (e) {
     console.error('worldInfoStore – loadWorldInfos:', e);
   }
-}
\ No newline at end of file
+}
+/** Books active in every chat, on top of each character's own. */
+export function getGlobalWorldInfos(): Promise<string[]> {
+  return invoke<string[]>('get_global_world_infos');
+}
+
+export function setGlobalWorldInfos(worldInfoIds: string[]): Promise<void> {
+  return invoke('set_global_world_infos', { worldInfoIds });
+}
+
+/** Books attached to a single chat. Clones of the chat keep them. */
+export function getChatWorldInfos(chatId: string): Promise<string[]> {
+  return invoke<string[]>('get_chat_world_infos', { chatId });
+}
+
+export function setChatWorldInfos(chatId: string, worldInfoIds: string[]): Promise<void> {
+  return invoke('set_chat_world_infos', { chatId, worldInfoIds });
+}
+
+/** Books active whenever the persona is. */
+export function getRoleWorldInfos(roleId: string): Promise<string[]> {
+  return invoke<string[]>('get_role_world_infos', { roleId });
+}
+
+export function setRoleWorldInfos(roleId: string, worldInfoIds: string[]): Promise<void> {
+  return invoke('set_role_world_infos', { roleId, worldInfoIds });
+}
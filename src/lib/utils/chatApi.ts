@@ -1,9 +1,6 @@
 import { invoke } from '@tauri-apps/api/core';
-import { buildSystemPrompt, buildWiString, buildWorldInfoBlock } from '$lib/utils/promptBuilder';
 import { worldInfoState } from '$lib/stores/worldInfoStore.svelte';
-import { chatState } from '$lib/stores/chatStore.svelte';
 import type { ApiSettings } from '$lib/stores/appState.svelte';
-import type { Message } from '$lib/stores/chatStore.svelte';
 
 export interface GenerationCallbacks {
     onStreamUpdate:        (text: string) => void;
@@ -20,9 +17,13 @@ export interface GenerationOptions {
         world_info_ids?: string[];
     } | null;
     apiSettings:    ApiSettings;
-    recentMessages: Message[];
+    /** The chat whose prompt the backend builds from the database. */
+    chatId:         string;
+    /** Ends the history right before this message (retry / swipe). */
+    beforeMessageId?: string;
     /** Include a new user prompt at the end (normal send). Omit for retry. */
     userPrompt?:    string;
+    roleId?:        string | null;
     role?: {
         name?:     string;
         bio?:      string;
@@ -36,9 +37,57 @@ export interface ChatMessage {
     content: string;
 }
 
-const START_ROLEPLAY_MARKER = '[Start Roleplay]';
+/** Token event payload — every stream is tagged with its generation ID. */
+export interface StreamTokenPayload {
+    generation_id: string;
+    token:         string;
+}
+
+/** Token usage and timing of one response, as returned by call_ai_api. */
+export interface GenerationStats {
+    model:                    string;
+    prompt_tokens:            number | null;
+    completion_tokens:        number | null;
+    total_tokens:             number | null;
+    /** completion_tokens was counted locally because the server sent no usage. */
+    estimated:                boolean;
+    time_to_first_token_ms:   number | null;
+    duration_ms:              number;
+    tokens_per_second:        number | null;
+    prompt_tokens_per_second: number | null;
+}
+
+interface GenerationResult {
+    generation_id: string;
+    stats:         GenerationStats | null;
+    reasoning:     string | null;
+    cancelled:     boolean;
+}
+
+export interface GenerationOutput {
+    text:         string;
+    stats:        GenerationStats | null;
+    /** Reasoning behind the reply; stored with the message, never sent back in prompts. */
+    reasoning:    string | null;
+    /** Links the saved reply to the request recorded for the prompt inspector. */
+    generationId: string;
+}
+
 const DEFAULT_THINKING_BUDGET = 2500;
 
+/** ID of the chat reply currently streaming, so Stop only hits that one. */
+let activeGenerationId: string | null = null;
+
+/**
+ * Stops the chat reply started by runGeneration(), leaving any parallel
+ * generation (e.g. a rolling summary) running.
+ */
+export async function stopActiveGeneration(): Promise<void> {
+    if (activeGenerationId) {
+        await invoke('stop_generation', { generationId: activeGenerationId });
+    }
+}
+
 /**
  * Escapes a string for safe use inside a RegExp.
  */
@@ -117,6 +166,18 @@ export function processThinkingOutput(raw: string, isFinished: boolean): {
     return { text, isThinking: false };
 }
 
+/**
+ * Returns the text of a leading <think>…</think> block, for backends that
+ * inline reasoning into the reply instead of using a separate channel.
+ */
+function extractThinkingContent(raw: string): string | null {
+    const end = raw.indexOf('</think>');
+    if (end < 0) return null;
+    const start = raw.indexOf('<think>');
+    const inner = raw.slice(start >= 0 && start < end ? start + '<think>'.length : 0, end).trim();
+    return inner || null;
+}
+
 /**
  * Strips <|channel>…<channel|> blocks from a string. While streaming, an
  * orphaned opening tag is left untouched since the closing tag may still
@@ -157,122 +218,453 @@ export function stripThinkingContent(content: string): string {
     return result.trimStart();
 }
 
-export function buildApiMessages(options: GenerationOptions): ChatMessage[] {
-    const { character, apiSettings, recentMessages, userPrompt, role } = options;
-
-    const worldInfoIds = character?.world_info_ids ?? [];
-    const relevantEntries = worldInfoState.allWorldInfos
-        .filter(wi => worldInfoIds.includes(wi.id))
-        .flatMap(wi => wi.entries);
-    const recentContext = [
-        ...recentMessages.slice(-10).map(m =>
-            m.role === 'assistant' ? stripThinkingContent(m.content) : m.content
-        ),
-        userPrompt ?? '',
-    ].join(' ');
-
-    const charName = character?.name || 'Unknown';
-    const userName = role?.name || 'User';
-
-    // Static block: core instructions + character card + user role.
-    // No world info here — see the layout note above.
-    const baseSystemPrompt = buildSystemPrompt({
-        charName,
-        desc:         character?.desc,
-        personality:  character?.personality,
-        scenario:     character?.scenario,
-        example:      character?.mes_example,
-        lang:         apiSettings.aiLanguage || 'English',
-        userName,
-        userBio:      role?.bio,
-        userPronouns: role?.pronouns,
-        modelType:    'ollama',
-    });
+/**
+ * prompt_options for a generation: the backend builds the prompt from the
+ * chat; the character, role and their world info are only sent as fallbacks
+ * for built-ins that aren't in the database.
+ */
+function promptOptions(options: GenerationOptions): PromptOptions {
+    const worldInfoIds = options.character?.world_info_ids ?? [];
+    return {
+        role_id:           options.roleId ?? null,
+        language:          options.apiSettings.aiLanguage || undefined,
+        user_prompt:       options.userPrompt,
+        before_message_id: options.beforeMessageId,
+        model:             options.apiSettings.model,
+        max_recursion:     options.apiSettings.worldInfoMaxRecursion,
+        character_memory:  options.apiSettings.characterMemory,
+        character:         options.character,
+        role:              options.role,
+        world_infos:       worldInfoState.allWorldInfos.filter(wi => worldInfoIds.includes(wi.id)),
+    };
+}
 
-    const { currentSummary, lastSummarizedMessageId } = chatState.summaryMeta;
+/**
+ * call_ai_api / generate_into_chat payload for a chat-style generation. With
+ * `options`, `messages` stays empty and the backend builds the chat's prompt.
+ */
+function buildPayload(
+    apiSettings:  ApiSettings,
+    generationId: string,
+    label:        string,
+    options?:     GenerationOptions,
+) {
+    const thinkingBudget = apiSettings.thinkingBudget ?? DEFAULT_THINKING_BUDGET;
+    const effectiveMaxTokens = apiSettings.isThinkingModel
+        ? apiSettings.maxTokens + thinkingBudget
+        : apiSettings.maxTokens;
+
+    return {
+        provider:           apiSettings.provider,
+        text_completion:    apiSettings.textCompletion,
+        instruct_template:  apiSettings.instructTemplate,
+        custom_template:    apiSettings.customTemplate || undefined,
+        url:                apiSettings.url,
+        api_key:            apiSettings.apiKey,
+        model:              apiSettings.model,
+        messages:           [],
+        chat_id:            options?.chatId,
+        prompt_options:     options ? promptOptions(options) : undefined,
+        temperature:        apiSettings.temperature,
+        max_tokens:         effectiveMaxTokens,
+        presence_penalty:   apiSettings.presencePenalty,
+        top_p:              apiSettings.topP,
+        top_k:              apiSettings.topK,
+        min_p:              apiSettings.minP,
+        // KoboldCpp has neither, only its own repetition penalty (rep_pen),
+        // which is the setting the others get as presence_penalty.
+        frequency_penalty:  apiSettings.provider === 'koboldcpp' ? undefined : apiSettings.frequencyPenalty,
+        rep_pen:            apiSettings.presencePenalty,
+        is_thinking_model:  apiSettings.isThinkingModel,
+        thinking_budget:    apiSettings.isThinkingModel ? thinkingBudget : undefined,
+        // Only read by the native Ollama provider, whose own default is 2048.
+        num_ctx:            apiSettings.contextLimit,
+        generation_id:      generationId,
+        label,
+    };
+}
 
-    // Append the rolling summary to the single system message instead of
-    // injecting a second system turn — avoids "No user query found" errors
-    // from models that expect exactly one system message (Qwen, Mistral, …).
-    const fullSystemContent = currentSummary
-        ? `${baseSystemPrompt}\n\n[Previous conversation summary:\n${currentSummary}]`
-        : baseSystemPrompt;
+/** Returned by generate_into_chat once the generation has started. */
+export interface BackgroundGeneration {
+    generation_id: string;
+    message_id:    string;
+    swipe_index:   number;
+}
 
-    // Only send messages that haven't been compressed into the summary yet.
-    const lastSumIdx  = lastSummarizedMessageId
-        ? recentMessages.findIndex(m => m.id === lastSummarizedMessageId)
-        : -1;
-    const newMessages = recentMessages.slice(lastSumIdx + 1);
+/** Payload of the 'ai-generation-finished' event. */
+export interface GenerationFinishedPayload extends BackgroundGeneration {
+    status:    'complete' | 'interrupted';
+    /** Nothing was generated, so the empty message/variant was removed again. */
+    discarded: boolean;
+    stats:     GenerationStats | null;
+    error:     { kind: string; message?: string } | null;
+}
 
-    const messages: ChatMessage[] = [
-        { role: 'system', content: fullSystemContent },
-    ];
+/**
+ * Starts a generation that the backend writes straight into the chat, so
+ * it survives leaving the chat or reloading the UI. Without `messageId` a
+ * new assistant message is created; with it, the reply becomes a new swipe
+ * variant. Tokens still arrive as 'ai-token' events tagged with the returned
+ * generation_id; 'ai-generation-finished' fires once the row is final.
+ */
+export async function startBackgroundGeneration(
+    options:    GenerationOptions,
+    messageId?: string,
+): Promise<BackgroundGeneration> {
+    const { chatId } = options;
+    return invoke<BackgroundGeneration>('generate_into_chat', {
+        payload: buildPayload(options.apiSettings, crypto.randomUUID(), 'chat', options),
+        chatId,
+        messageId,
+    });
+}
+
+export interface SwipeResult {
+    choice_index:  number;
+    generation_id: string;
+    /** Index of the saved variant; null if this choice produced no text. */
+    swipe_index:   number | null;
+    stats:         GenerationStats | null;
+    error:         { kind: string; message?: string } | null;
+}
 
-    messages.push(...newMessages.map(msg => ({
-        role:    msg.role as ChatRole,
-        content: msg.role === 'assistant' ? stripThinkingContent(msg.content) : msg.content,
-    })));
+export interface SwipeBatchResult {
+    generation_id: string;
+    swipes:        SwipeResult[];
+    /** Choice indexes that broke off or couldn't be saved; see SwipeResult.error. */
+    failed:        number[];
+    cancelled:     boolean;
+}
+
+/**
+ * Generates `count` swipe variants for an assistant message in one go and
+ * saves each finished one on the backend. `onChoiceUpdate` receives the text
+ * streamed so far per choice index.
+ */
+export async function generateSwipes(
+    options:        GenerationOptions,
+    messageId:      string,
+    count:          number,
+    onChoiceUpdate: (choiceIndex: number, text: string) => void,
+    parallel = false,
+): Promise<SwipeBatchResult> {
+    const generationId = crypto.randomUUID();
+    activeGenerationId = generationId;
+
+    const buffers = new Map<number, string>();
+    const { listen } = await import('@tauri-apps/api/event');
+    const prefix = `${generationId}:`;
+    const unlisten = await listen<StreamTokenPayload>('ai-token', (event) => {
+        const id = event.payload.generation_id;
+        if (!id.startsWith(prefix)) return;
+        const index = Number(id.slice(prefix.length));
+        const text = (buffers.get(index) ?? '') + event.payload.token;
+        buffers.set(index, text);
+        onChoiceUpdate(index, text);
+    });
 
-    if (userPrompt) {
-        messages.push({ role: 'user', content: userPrompt });
+    try {
+        return await invoke<SwipeBatchResult>('generate_swipes', {
+            payload: buildPayload(options.apiSettings, generationId, 'swipe', options),
+            messageId,
+            count,
+            parallel,
+        });
+    } finally {
+        if (activeGenerationId === generationId) activeGenerationId = null;
+        unlisten();
     }
+}
 
-    // Some model templates (e.g. Qwen via LM Studio) require the first
-    // non-system turn to be a user message.
-    const firstNonSystem = messages.find(m => m.role !== 'system');
-    if (firstNonSystem?.role === 'assistant') {
-        const systemIndex = messages.findLastIndex(m => m.role === 'system');
-        messages.splice(systemIndex + 1, 0, { role: 'user', content: START_ROLEPLAY_MARKER });
+export interface ContinueResult {
+    generation_id: string;
+    /** Full message text after continuing, as saved. */
+    content:       string;
+    /** Stats of the continuation alone. */
+    stats:         GenerationStats | null;
+    cancelled:     boolean;
+}
+
+/**
+ * Extends an assistant message that got cut off. The backend sends its
+ * current text as a prefill and appends the new tokens to the active variant
+ * itself, with the continuation's stats and reasoning; `onStreamUpdate`
+ * receives only the continuation streamed so far.
+ */
+export async function continueMessage(
+    options:        GenerationOptions,
+    messageId:      string,
+    onStreamUpdate: (continuation: string) => void,
+): Promise<ContinueResult> {
+    const generationId = crypto.randomUUID();
+    activeGenerationId = generationId;
+
+    let buffer = '';
+    const { listen } = await import('@tauri-apps/api/event');
+    const unlisten = await listen<StreamTokenPayload>('ai-token', (event) => {
+        if (event.payload.generation_id !== generationId) return;
+        buffer += event.payload.token;
+        onStreamUpdate(buffer);
+    });
+
+    try {
+        return await invoke<ContinueResult>('continue_generation', {
+            payload: buildPayload(options.apiSettings, generationId, 'continue', options),
+            messageId,
+        });
+    } finally {
+        if (activeGenerationId === generationId) activeGenerationId = null;
+        unlisten();
     }
+}
 
-    // World info: computed last, attached only to the current turn (see
-    // layout note above) instead of the cached system message.
-    const worldInfoBlock = buildWorldInfoBlock(
-        buildWiString(relevantEntries, 'before', recentContext),
-        buildWiString(relevantEntries, 'after',  recentContext),
-        charName,
-        userName,
-        'ollama',
-    );
+export interface ImpersonateResult {
+    generation_id: string;
+    draft:         string;
+    stats:         GenerationStats | null;
+    cancelled:     boolean;
+}
 
-    if (worldInfoBlock) {
-        const lastUserIdx = messages.findLastIndex(m => m.role === 'user');
-        if (lastUserIdx !== -1) {
-            messages[lastUserIdx] = {
-                ...messages[lastUserIdx],
-                content: `${worldInfoBlock}\n\n${messages[lastUserIdx].content}`,
-            };
-        } else {
-            // No user turn to attach to (shouldn't normally happen) — fall
-            // back to a standalone message so the info isn't silently lost.
-            messages.push({ role: 'user', content: worldInfoBlock });
-        }
+/**
+ * Drafts the user's next message from the given persona. Streams on its own
+ * 'ai-impersonate-token' event, so it never mixes with a chat reply; the
+ * draft is only returned, not saved.
+ */
+export async function impersonate(
+    options:        GenerationOptions,
+    roleId:         string | null,
+    onStreamUpdate: (draft: string) => void,
+): Promise<ImpersonateResult> {
+    const generationId = crypto.randomUUID();
+
+    let buffer = '';
+    const { listen } = await import('@tauri-apps/api/event');
+    const unlisten = await listen<StreamTokenPayload>('ai-impersonate-token', (event) => {
+        if (event.payload.generation_id !== generationId) return;
+        buffer += event.payload.token;
+        onStreamUpdate(buffer);
+    });
+
+    try {
+        return await invoke<ImpersonateResult>('impersonate', {
+            payload: buildPayload(options.apiSettings, generationId, 'impersonate', options),
+            roleId,
+        });
+    } finally {
+        unlisten();
     }
+}
 
-    return messages;
+/** Options for build_prompt; also accepted as `prompt_options` with a `chat_id` payload. */
+export interface PromptOptions {
+    role_id?:           string | null;
+    language?:          string;
+    user_prompt?:       string;
+    /** Ends the history right before this message (retry / swipe). */
+    before_message_id?: string;
+    /** Picks the tokenizer for the counts. */
+    model?:             string;
+    /** Rescans of activated world info for further keys; defaults to the world_info_max_recursion setting. */
+    max_recursion?:     number;
+    /** Seed for world info probability rolls; defaults to one per chat and message. */
+    seed?:              number;
+    /** Notes placed at a depth in the history, like world info positioned 'at_depth'. */
+    injections?:        { content: string; depth?: number; role?: 'system' | 'user' | 'assistant'; order?: number }[];
+    /** Chunks of summarized messages to recall; defaults to the memory_top_k setting (0 = off). */
+    memory_top_k?:      number;
+    /** Token budget of recalled chunks; defaults to the memory_token_budget setting. */
+    memory_token_budget?: number;
+    /** Adds the character's notes from earlier chats; defaults to the character_memory setting (off). */
+    character_memory?:  boolean;
+    /** Fallbacks for built-ins that aren't in the database. */
+    character?:         GenerationOptions['character'];
+    role?:              GenerationOptions['role'];
+    world_infos?:       { id: string; entries: unknown[] }[];
+}
+
+export interface BuiltPrompt {
+    messages:     ChatMessage[];
+    /** instructions, character, persona, character_memory, summary, world_info, memory, notes, history, user_prompt */
+    sections:     { name: string; tokens: number }[];
+    total_tokens: number;
+}
+
+/**
+ * Builds a chat's prompt on the backend straight from the database, with a
+ * token count per section.
+ */
+export async function buildPrompt(chatId: string, options: PromptOptions = {}): Promise<BuiltPrompt> {
+    return invoke<BuiltPrompt>('build_prompt', { chatId, options });
+}
+
+export type WorldInfoEntryStatus =
+    | 'activated' | 'not_triggered' | 'disabled' | 'delayed' | 'cooldown'
+    | 'probability_failed' | 'lost_to_group' | 'over_budget';
+
+export interface WorldInfoTestResult {
+    entries: {
+        book_id:    string;
+        entry_id:   string;
+        comment:    string;
+        status:     WorldInfoEntryStatus;
+        /** What made it a candidate; null if it never triggered. */
+        trigger:    { kind: 'key'; key: string }
+                  | { kind: 'semantic'; similarity: number }
+                  | { kind: 'no_keys' | 'constant' | 'sticky' }
+                  | null;
+        scan_depth: number;
+        /** Recursion step it triggered in; 0 = the messages themselves. */
+        step:       number | null;
+        tokens:     number | null;
+    }[];
+    scanned_messages: number;
+    /** The block prepended to the last user turn. */
+    attached: string;
+    at_depth: { depth: number; role: 'system' | 'user' | 'assistant'; content: string }[];
+}
+
+/**
+ * Shows which world info entries a chat and/or a piece of text would
+ * activate, and why the others didn't. Uses the chat character's books when
+ * `worldInfoIds` is empty. Nothing is generated or saved.
+ */
+export async function testWorldInfo(
+    target: { chatId?: string; text?: string },
+    worldInfoIds: string[] = [],
+    options: PromptOptions = {},
+): Promise<WorldInfoTestResult> {
+    return invoke<WorldInfoTestResult>('test_world_info', {
+        chatId: target.chatId ?? null,
+        text: target.text ?? null,
+        worldInfoIds,
+        options,
+    });
+}
+
+/** A request as recorded for the prompt inspector (API key removed). */
+export interface GenerationRequest {
+    generation_id: string;
+    request: {
+        provider: string;
+        method:   string;
+        endpoint: string;
+        model:    string;
+        body:     unknown;
+    };
+    created_at: string;
+}
+
+/**
+ * Fetches the request that produced a swipe variant, or null if none was
+ * recorded or it has been pruned (see the prompt_inspector_retention_days setting).
+ */
+export async function getGenerationRequest(messageId: string, variant: number): Promise<GenerationRequest | null> {
+    return invoke<GenerationRequest | null>('get_generation_request', { messageId, variant });
+}
+
+export interface MemoryChunk {
+    position:         number;
+    first_message_id: string;
+    last_message_id:  string;
+    content:          string;
+}
+
+/** The summarized messages of a chat stored for long-term memory recall, oldest first. */
+export async function getMemoryChunks(chatId: string): Promise<MemoryChunk[]> {
+    return invoke<MemoryChunk[]>('get_memory_chunks', { chatId });
+}
+
+/** Forgets a chat's memory chunks; they're rebuilt on the next generation. */
+export async function clearMemory(chatId: string): Promise<void> {
+    return invoke('clear_memory', { chatId });
+}
+
+export interface CharacterMemory {
+    id:             string;
+    character_id:   string;
+    /** null = remembered whichever persona the user plays. */
+    role_id:        string | null;
+    content:        string;
+    /** The chat it was extracted from; null for notes written by hand. */
+    source_chat_id: string | null;
+    enabled:        boolean;
+    created_at:     string;
+    updated_at:     string;
+}
+
+export interface ExtractMemoriesResult {
+    generation_id: string;
+    /** The notes that were saved. */
+    memories:      CharacterMemory[];
+    cancelled:     boolean;
+}
+
+/**
+ * Has the model note what the chat's character should remember from it in
+ * later chats, and saves the notes. With a roleId they only apply to that
+ * persona. Streams on its own 'ai-memory-token' event.
+ */
+export async function extractCharacterMemories(
+    apiSettings: ApiSettings,
+    chatId:      string,
+    roleId:      string | null,
+): Promise<ExtractMemoriesResult> {
+    const generationId = crypto.randomUUID();
+    return invoke<ExtractMemoriesResult>('extract_character_memories', {
+        payload: buildPayload(apiSettings, generationId, 'memory'),
+        chatId,
+        roleId,
+    });
+}
+
+export async function getCharacterMemories(characterId: string): Promise<CharacterMemory[]> {
+    return invoke<CharacterMemory[]>('get_character_memories', { characterId });
+}
+
+export async function createCharacterMemory(
+    characterId: string,
+    roleId:      string | null,
+    content:     string,
+): Promise<CharacterMemory> {
+    return invoke<CharacterMemory>('create_character_memory', {
+        payload: { character_id: characterId, role_id: roleId, content },
+    });
+}
+
+export async function updateCharacterMemory(
+    id:     string,
+    update: { content: string; role_id: string | null; enabled: boolean },
+): Promise<void> {
+    return invoke('update_character_memory', { id, payload: update });
+}
+
+export async function deleteCharacterMemory(id: string): Promise<void> {
+    return invoke('delete_character_memory', { id });
 }
 
 /**
  * Calls the AI API and streams the response.
  *
- * Important: call checkAndSummarizeIfNeeded() and await it BEFORE calling
- * this function. The two invoke('call_ai_api') calls must never overlap —
- * both share the Tauri 'ai-token' / 'ai-thinking-token' SSE channels.
+ * Each call gets its own generation ID and only listens to events tagged
+ * with it, so it can safely run alongside a rolling summary.
+ * Resolves with the final text and the response's token usage/timing.
  */
 export async function runGeneration(
     options:   GenerationOptions,
     callbacks: GenerationCallbacks,
-): Promise<string> {
+): Promise<GenerationOutput> {
     const { apiSettings } = options;
 
-    const messages = buildApiMessages(options);
-
     let rawBuffer      = '';
     let thinkingBuffer = '';
 
+    const generationId = crypto.randomUUID();
+    activeGenerationId = generationId;
+
     const { listen } = await import('@tauri-apps/api/event');
 
-    const unlistenToken = await listen<{ token: string }>('ai-token', (event) => {
+    const unlistenToken = await listen<StreamTokenPayload>('ai-token', (event) => {
+        if (event.payload.generation_id !== generationId) return;
         rawBuffer += event.payload.token;
 
         if (apiSettings.isThinkingModel) {
@@ -288,47 +680,35 @@ export async function runGeneration(
 
     // Backend emits reasoning tokens (delta.reasoning_content) on their own
     // event so the UI can know it's "thinking" without relying on tag-parsing.
-    const unlistenThinking = await listen<{ token: string }>('ai-thinking-token', (event) => {
+    const unlistenThinking = await listen<StreamTokenPayload>('ai-thinking-token', (event) => {
+        if (event.payload.generation_id !== generationId) return;
         thinkingBuffer += event.payload.token;
         callbacks.onThinkingPhaseChange(true);
     });
 
     try {
-        const thinkingBudget = apiSettings.thinkingBudget ?? DEFAULT_THINKING_BUDGET;
-        const effectiveMaxTokens = apiSettings.isThinkingModel
-            ? apiSettings.maxTokens + thinkingBudget
-            : apiSettings.maxTokens;
-
-        await invoke('call_ai_api', {
-            payload: {
-                url:                apiSettings.url,
-                api_key:            apiSettings.apiKey,
-                model:              apiSettings.model,
-                messages,
-                temperature:        apiSettings.temperature,
-                max_tokens:         effectiveMaxTokens,
-                presence_penalty:   apiSettings.presencePenalty,
-                top_p:              apiSettings.topP,
-                top_k:              apiSettings.topK,
-                min_p:              apiSettings.minP,
-                frequency_penalty:  apiSettings.frequencyPenalty,
-                is_thinking_model:  apiSettings.isThinkingModel,
-                thinking_budget:    apiSettings.isThinkingModel ? thinkingBudget : undefined,
-            },
+        const result = await invoke<GenerationResult>('call_ai_api', {
+            payload: buildPayload(apiSettings, generationId, 'chat', options),
         });
 
         if (apiSettings.isThinkingModel) {
             const { text } = processThinkingOutput(rawBuffer, true);
             callbacks.onStreamUpdate(text);
-            return text || rawBuffer;
+            return {
+                text:         text || rawBuffer,
+                stats:        result.stats,
+                reasoning:    result.reasoning ?? extractThinkingContent(rawBuffer),
+                generationId,
+            };
         }
 
         callbacks.onStreamUpdate(rawBuffer);
-        return rawBuffer;
+        return { text: rawBuffer, stats: result.stats, reasoning: result.reasoning, generationId };
     } finally {
         // Always cleared, even on error — otherwise the UI can get stuck
         // showing a "thinking" state after a failed request.
         callbacks.onThinkingPhaseChange(false);
+        if (activeGenerationId === generationId) activeGenerationId = null;
         unlistenToken();
         unlistenThinking();
     }
@@ -3,8 +3,8 @@ import { listen } from '@tauri-apps/api/event';
 import { appState } from '$lib/stores/appState.svelte';
 import { chatState } from '$lib/stores/chatStore.svelte';
 import type { Message } from '$lib/stores/chatStore.svelte';
-import { buildApiMessages } from '$lib/utils/chatApi';
-import type { GenerationOptions } from '$lib/utils/chatApi';
+import { buildPrompt } from '$lib/utils/chatApi';
+import type { GenerationOptions, StreamTokenPayload } from '$lib/utils/chatApi';
 import { processThinkingOutput, stripThinkingContent } from '$lib/utils/chatApi';
 
 const DEFAULT_CONTEXT_LIMIT = 4096;
@@ -60,8 +60,9 @@ async function countMessagesTokens(messages: { role: string; content: string }[]
 /**
  * Returns the token budget available for compressible middle messages.
  *
- * Builds the full prompt prefix (system + world info + summary + tail) and
- * measures it directly instead of using a static overhead constant.
+ * Builds the chat's prompt on the backend (system + world info + summary)
+ * and measures it directly instead of using a static overhead constant; of
+ * the unsummarized history only the tail is counted.
  */
 async function computeAvailableMiddleBudget(
     options: GenerationOptions,
@@ -70,20 +71,21 @@ async function computeAvailableMiddleBudget(
     const contextLimit = appState.apiSettings?.contextLimit ?? DEFAULT_CONTEXT_LIMIT;
 
     // userPrompt is omitted. it's either already in the tail or appended later by runGeneration.
-    const probeMessages = buildApiMessages({
-        ...options,
-        recentMessages: tail,
-        userPrompt:     undefined,
+    const probe = await buildPrompt(options.chatId, {
+        role_id:   options.roleId ?? null,
+        model:     options.apiSettings.model,
+        character: options.character,
+        role:      options.role,
     });
-console.debug('[RollingSummary] probeMessages count=%d, contents:', 
-    probeMessages.length, 
-    probeMessages.map(m => `${m.role}(${m.content.length}chars)`)
+    const historyTokens = probe.sections.find(s => s.name === 'history')?.tokens ?? 0;
+console.debug('[RollingSummary] probe sections:',
+    probe.sections.map(s => `${s.name}(${s.tokens})`)
 );
     const baseMaxTokens    = appState.apiSettings?.maxTokens ?? 300;
     const thinkingBudget   = appState.apiSettings?.isThinkingModel
         ? (appState.apiSettings?.thinkingBudget ?? 2500)
         : 0;
-    const fixedTokens     = await countMessagesTokens(probeMessages);
+    const fixedTokens     = probe.total_tokens - historyTokens + await countMessagesTokens(tail);
     const responseReserve  = baseMaxTokens + thinkingBudget + RESPONSE_RESERVE_EXTRA;
     const budget          = contextLimit - responseReserve - fixedTokens;
 
@@ -142,18 +144,24 @@ async function generateSummary(
     ];
 
     let rawBuffer = '';
-    const unlisten = await listen<{ token: string }>('ai-token', (event) => {
+    const generationId = crypto.randomUUID();
+    const unlisten = await listen<StreamTokenPayload>('ai-token', (event) => {
+        if (event.payload.generation_id !== generationId) return;
         rawBuffer += event.payload.token;
     });
     // Summary generation ignores reasoning text entirely (only the final
     // content matters here) but the listener still needs registering so the
     // 'ai-thinking-token' event doesn't accumulate unhandled while thinking
     // is enabled for this call.
-    const unlistenThinking = await listen<{ token: string }>('ai-thinking-token', () => {});
+    const unlistenThinking = await listen<StreamTokenPayload>('ai-thinking-token', () => {});
 
     try {
         await invoke('call_ai_api', {
             payload: {
+                provider:           apiSettings.provider,
+                text_completion:    apiSettings.textCompletion,
+                instruct_template:  apiSettings.instructTemplate,
+                custom_template:    apiSettings.customTemplate || undefined,
                 url:                apiSettings.url,
                 api_key:            apiSettings.apiKey,
                 model:              apiSettings.model,
@@ -165,6 +173,8 @@ async function generateSummary(
                 presence_penalty:   0,
                 is_thinking_model:  apiSettings.isThinkingModel,
                 thinking_budget:    apiSettings.isThinkingModel ? THINKING_OVERHEAD : undefined,
+                generation_id:      generationId,
+                label:              'summary',
             },
         });
     } finally {
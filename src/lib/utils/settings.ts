@@ -30,7 +30,18 @@ export async function getSetting(key: string): Promise<string | null> {
   return found ? found.value : null;
 }
 
+// Same default as DEFAULT_MEMORY_BUDGET in src-tauri/src/prompt/mod.rs.
+export const DEFAULT_MEMORY_TOKEN_BUDGET = 600;
+
+/** Long-term memory recall: summarized chunks per prompt (0 = off) and their token budget. */
+export async function saveMemorySettings(topK: number, tokenBudget: number) {
+  await Promise.all([
+    saveSetting("memory_top_k", Math.max(0, Math.round(topK || 0))),
+    saveSetting("memory_token_budget", Math.max(0, Math.round(tokenBudget ?? DEFAULT_MEMORY_TOKEN_BUDGET))),
+  ]);
+}
+
 // Does NOT catch — throws the real Rust error string so the UI can display it directly.
-export async function fetchModels(url: string, apiKey: string): Promise<string[]> {
-  return await invoke<string[]>("fetch_models", { url, apiKey });
+export async function fetchModels(url: string, apiKey: string, provider?: string): Promise<string[]> {
+  return await invoke<string[]>("fetch_models", { url, apiKey, provider });
 }
\ No newline at end of file